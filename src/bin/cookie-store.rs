@@ -0,0 +1,536 @@
+//! `cookie-store`: inspect, maintain, and convert persisted cookie jars from the command line.
+//! Requires feature `cli`.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::str::FromStr;
+
+use cookie_store::{CookieExpiration, CookieQuery, CookieStore, Error, StoreError};
+use time::format_description::well_known::Rfc3339;
+use url::Url;
+
+const USAGE: &str = "\
+Usage: cookie-store [OPTIONS] <FILE> <COMMAND>
+
+Reads the cookie jar FILE (`-` for stdin), including expired and non-persistent cookies.
+
+Commands:
+  list [--domain <DOMAIN>] [--subdomains] [--name <PATTERN>] [--expired]
+                        List cookies in a table, optionally filtered by domain (and its
+                        subdomains) and name (`*` matches any characters); --expired includes
+                        expired cookies
+  header <URL>          Print the value of the `Cookie` header that would be sent to URL
+  purge                 Remove expired cookies
+  delete <DOMAIN> [--subdomains]
+                        Remove the cookies of DOMAIN (and its subdomains)
+  convert --to <FORMAT> Write the jar in FORMAT
+
+Options:
+  -f, --format <FORMAT> The format of FILE: `json` (see `cookie_store::serde::json`), `ron`
+                        (see `cookie_store::serde::ron`), or `legacy` (see
+                        `CookieStore::load_json`). Defaults to `ron` for `.ron` files,
+                        otherwise `json`
+  -o, --output <PATH>   Where `purge`, `delete`, and `convert` write the jar (`-` for stdout).
+                        `purge` and `delete` default to replacing FILE; `convert` to stdout
+  -h, --help            Print this help";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Json,
+    Ron,
+    Legacy,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "json" => Ok(Format::Json),
+            "ron" => Ok(Format::Ron),
+            "legacy" => Ok(Format::Legacy),
+            _ => Err(format!(
+                "unknown format '{s}'; expected json, ron, or legacy"
+            )),
+        }
+    }
+}
+
+impl Format {
+    /// The format implied by the extension of `path`
+    fn for_path(path: &str) -> Format {
+        match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("ron") => Format::Ron,
+            _ => Format::Json,
+        }
+    }
+
+    fn load<R: BufRead>(self, reader: R) -> Result<CookieStore, StoreError> {
+        match self {
+            Format::Json => cookie_store::serde::json::load_all(reader),
+            Format::Ron => cookie_store::serde::ron::load_all(reader),
+            #[allow(deprecated)]
+            Format::Legacy => CookieStore::load_json_all(reader),
+        }
+    }
+
+    fn save<W: Write>(self, store: &CookieStore, writer: &mut W) -> Result<(), StoreError> {
+        match self {
+            Format::Json => {
+                cookie_store::serde::json::save_incl_expired_and_nonpersistent(store, writer)
+            }
+            Format::Ron => {
+                cookie_store::serde::ron::save_incl_expired_and_nonpersistent(store, writer)
+            }
+            #[allow(deprecated)]
+            Format::Legacy => store.save_incl_expired_and_nonpersistent_json(writer),
+        }
+    }
+
+    /// Replace the file at `path` with `store`; atomically, where supported by the format
+    fn save_to_path(self, store: &CookieStore, path: &str) -> Result<(), StoreError> {
+        match self {
+            Format::Json => cookie_store::serde::json::save_incl_expired_and_nonpersistent_to_path(
+                store, path, 0,
+            ),
+            Format::Ron => cookie_store::serde::ron::save_incl_expired_and_nonpersistent_to_path(
+                store, path, 0,
+            ),
+            Format::Legacy => {
+                let mut writer = BufWriter::new(File::create(path)?);
+                self.save(store, &mut writer)?;
+                writer.flush().map_err(Into::into)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    List {
+        domain: Option<String>,
+        subdomains: bool,
+        name: Option<String>,
+        expired: bool,
+    },
+    Header(Url),
+    Purge,
+    Delete {
+        domain: String,
+        subdomains: bool,
+    },
+    Convert(Format),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Args {
+    input: String,
+    format: Option<Format>,
+    output: Option<String>,
+    command: Command,
+}
+
+/// Parse the command line arguments (excluding the program name); `Ok(None)` if help was
+/// requested
+fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Option<Args>, String> {
+    let mut positional = Vec::new();
+    let mut format = None;
+    let mut output = None;
+    let mut domain = None;
+    let mut name = None;
+    let mut to = None;
+    let mut subdomains = false;
+    let mut expired = false;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| {
+            args.next()
+                .ok_or_else(|| format!("{flag} requires a value"))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-f" | "--format" => format = Some(value(&arg)?.parse()?),
+            "-o" | "--output" => output = Some(value(&arg)?),
+            "--domain" => domain = Some(value(&arg)?),
+            "--name" => name = Some(value(&arg)?),
+            "--to" => to = Some(value(&arg)?.parse()?),
+            "--subdomains" => subdomains = true,
+            "--expired" => expired = true,
+            "-" => positional.push(arg),
+            flag if flag.starts_with('-') => return Err(format!("unknown option '{flag}'")),
+            _ => positional.push(arg),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let input = positional.next().ok_or("missing FILE")?;
+    let command = positional.next().ok_or("missing COMMAND")?;
+    let mut operand = |what: &str| {
+        positional
+            .next()
+            .ok_or_else(|| format!("{command} requires {what}"))
+    };
+    let command = match command.as_str() {
+        "list" => Command::List {
+            domain: domain.take(),
+            subdomains,
+            name: name.take(),
+            expired,
+        },
+        "header" => {
+            let url = operand("a URL")?;
+            Command::Header(Url::parse(&url).map_err(|e| format!("invalid URL '{url}': {e}"))?)
+        }
+        "purge" => Command::Purge,
+        "delete" => Command::Delete {
+            domain: operand("a DOMAIN")?,
+            subdomains,
+        },
+        "convert" => Command::Convert(to.take().ok_or("convert requires --to")?),
+        other => return Err(format!("unknown command '{other}'")),
+    };
+    if let Some(extra) = positional.next() {
+        return Err(format!("unexpected argument '{extra}'"));
+    }
+    let misplaced = match command {
+        Command::List { .. } => to.map(|_| "--to"),
+        Command::Delete { .. } => domain
+            .map(|_| "--domain")
+            .or(name.map(|_| "--name"))
+            .or(to.map(|_| "--to"))
+            .or(Some("--expired").filter(|_| expired)),
+        _ => domain
+            .map(|_| "--domain")
+            .or(name.map(|_| "--name"))
+            .or(to.map(|_| "--to"))
+            .or(Some("--subdomains").filter(|_| subdomains))
+            .or(Some("--expired").filter(|_| expired)),
+    };
+    if let Some(flag) = misplaced {
+        return Err(format!("{flag} is not valid for this command"));
+    }
+    if output.is_some() && matches!(command, Command::List { .. } | Command::Header(_)) {
+        return Err("--output is not valid for this command".to_owned());
+    }
+    Ok(Some(Args {
+        input,
+        format,
+        output,
+        command,
+    }))
+}
+
+fn expiration(expires: &CookieExpiration, expired: bool) -> String {
+    match *expires {
+        CookieExpiration::AtUtc(ref utc_tm) => {
+            let utc_tm = utc_tm
+                .format(&Rfc3339)
+                .unwrap_or_else(|_| utc_tm.to_string());
+            if expired {
+                format!("{utc_tm} (expired)")
+            } else {
+                utc_tm
+            }
+        }
+        CookieExpiration::SessionEnd => "session".to_owned(),
+    }
+}
+
+/// Write `rows` as a table, with columns padded to their widest cell
+fn write_table<W: Write>(writer: &mut W, rows: &[Vec<String>]) -> io::Result<()> {
+    let columns = rows.first().map_or(0, |r| r.len());
+    let widths = (0..columns)
+        .map(|i| rows.iter().map(|r| r[i].chars().count()).max().unwrap_or(0))
+        .collect::<Vec<_>>();
+    for row in rows {
+        let mut line = String::new();
+        for (i, (cell, width)) in row.iter().zip(&widths).enumerate() {
+            if i + 1 == columns {
+                line.push_str(cell);
+            } else {
+                line.push_str(&format!("{cell:<width$}  "));
+            }
+        }
+        writeln!(writer, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+fn list<W: Write>(store: &CookieStore, query: &CookieQuery, writer: &mut W) -> io::Result<()> {
+    let mut cookies = store.select(query).collect::<Vec<_>>();
+    cookies.sort_by(|a, b| {
+        (String::from(&a.domain), &a.path[..], a.name()).cmp(&(
+            String::from(&b.domain),
+            &b.path[..],
+            b.name(),
+        ))
+    });
+    let mut rows = vec![[
+        "DOMAIN", "PATH", "NAME", "VALUE", "SECURE", "HTTPONLY", "SAMESITE", "EXPIRES",
+    ]
+    .iter()
+    .map(|h| h.to_string())
+    .collect::<Vec<_>>()];
+    let flag = |set: Option<bool>| if set.unwrap_or(false) { "yes" } else { "no" }.to_owned();
+    rows.extend(cookies.into_iter().map(|c| {
+        vec![
+            String::from(&c.domain),
+            c.path.to_string(),
+            c.name().to_owned(),
+            c.value().to_owned(),
+            flag(c.secure()),
+            flag(c.http_only()),
+            c.same_site()
+                .map_or_else(|| "-".to_owned(), |s| s.to_string()),
+            expiration(&c.expires, c.is_expired()),
+        ]
+    }));
+    write_table(writer, &rows)
+}
+
+/// Write `store` to `output` (`-` for stdout)
+fn write_store<W: Write>(
+    store: &CookieStore,
+    format: Format,
+    output: &str,
+    stdout: &mut W,
+) -> Result<(), StoreError> {
+    if output == "-" {
+        format.save(store, stdout)
+    } else {
+        format.save_to_path(store, output)
+    }
+}
+
+fn run<W: Write>(args: &Args, stdout: &mut W) -> Result<(), Error> {
+    let format = args.format.unwrap_or_else(|| Format::for_path(&args.input));
+    let mut store = if args.input == "-" {
+        format.load(io::stdin().lock())?
+    } else {
+        format.load(BufReader::new(File::open(&args.input)?))?
+    };
+    let output = args.output.as_deref().unwrap_or(&args.input);
+    match args.command {
+        Command::List {
+            ref domain,
+            subdomains,
+            ref name,
+            expired,
+        } => {
+            let mut query = CookieQuery::new().include_expired(expired);
+            if let Some(domain) = domain {
+                query = query.domain(domain).include_subdomains(subdomains);
+            }
+            if let Some(name) = name {
+                query = query.name(name);
+            }
+            list(&store, &query, stdout)?;
+        }
+        Command::Header(ref url) => {
+            let mut header = String::new();
+            if store.write_cookie_header(url, &mut header)? > 0 {
+                writeln!(stdout, "{header}")?;
+            }
+        }
+        Command::Purge => {
+            let purged = store.purge_expired().len();
+            write_store(&store, format, output, stdout)?;
+            eprintln!("purged {purged} expired cookie(s)");
+        }
+        Command::Delete {
+            ref domain,
+            subdomains,
+        } => {
+            let removed = if subdomains {
+                let query = CookieQuery::new()
+                    .domain(domain)
+                    .include_subdomains(true)
+                    .include_expired(true);
+                store.remove_selected(&query).len()
+            } else {
+                store.remove_domain(domain).len()
+            };
+            write_store(&store, format, output, stdout)?;
+            eprintln!("deleted {removed} cookie(s)");
+        }
+        Command::Convert(to) => {
+            write_store(&store, to, args.output.as_deref().unwrap_or("-"), stdout)?;
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    match run(&args, &mut stdout).and_then(|_| stdout.flush().map_err(Into::into)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprint!("error: {e}");
+            let mut source = e.source();
+            while let Some(e) = source {
+                eprint!(": {e}");
+                source = e.source();
+            }
+            eprintln!();
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use cookie_store::{CookieQuery, CookieStore};
+    use url::Url;
+
+    use super::{parse_args, run, Args, Command, Format};
+
+    fn args(args: &str) -> Result<Option<Args>, String> {
+        parse_args(args.split_whitespace().map(str::to_owned))
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("cookie-store-cli-{}-{name}", std::process::id()))
+    }
+
+    fn run_to_string(line: &str) -> String {
+        let mut stdout = Vec::new();
+        run(&args(line).unwrap().unwrap(), &mut stdout).unwrap();
+        String::from_utf8(stdout).unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Args {
+                input: "jar.ron".to_owned(),
+                format: None,
+                output: None,
+                command: Command::List {
+                    domain: Some("example.com".to_owned()),
+                    subdomains: true,
+                    name: Some("sess*".to_owned()),
+                    expired: false,
+                },
+            },
+            args("jar.ron list --domain example.com --subdomains --name sess*")
+                .unwrap()
+                .unwrap()
+        );
+        assert_eq!(
+            Args {
+                input: "-".to_owned(),
+                format: Some(Format::Legacy),
+                output: Some("out.ron".to_owned()),
+                command: Command::Convert(Format::Ron),
+            },
+            args("-f legacy - convert --to ron -o out.ron")
+                .unwrap()
+                .unwrap()
+        );
+        assert_eq!(None, args("jar.json list --help").unwrap());
+        for (line, err) in [
+            ("", "missing FILE"),
+            ("jar.json", "missing COMMAND"),
+            ("jar.json frobnicate", "unknown command 'frobnicate'"),
+            ("jar.json header", "header requires a URL"),
+            ("jar.json convert", "convert requires --to"),
+            (
+                "jar.json convert --to xml",
+                "unknown format 'xml'; expected json, ron, or legacy",
+            ),
+            (
+                "jar.json purge --domain x",
+                "--domain is not valid for this command",
+            ),
+            (
+                "jar.json list -o out.json",
+                "--output is not valid for this command",
+            ),
+            ("jar.json delete a b", "unexpected argument 'b'"),
+            ("jar.json list --verbose", "unknown option '--verbose'"),
+            ("jar.json list --name", "--name requires a value"),
+        ] {
+            assert_eq!(Err(err.to_owned()), args(line), "{line}");
+        }
+    }
+
+    #[test]
+    fn commands() {
+        let jar = temp_path("jar.json");
+        let url = Url::parse("https://www.example.com/").unwrap();
+        let mut store = CookieStore::default();
+        for cookie in [
+            "session=1; Secure; HttpOnly",
+            "pref=dark; Max-Age=3600; SameSite=Lax",
+            "gone=x; Max-Age=3600",
+            "shared=2; Domain=example.com",
+        ] {
+            store.parse(cookie, &url).unwrap();
+        }
+        store
+            .parse("other=3", &Url::parse("http://other.test/").unwrap())
+            .unwrap();
+        store.expire_selected(&CookieQuery::new().name("gone"));
+        cookie_store::serde::json::save_incl_expired_and_nonpersistent_to_path(&store, &jar, 0)
+            .unwrap();
+        let jar = jar.to_str().unwrap();
+
+        let table = run_to_string(&format!("{jar} list --domain example.com --subdomains"));
+        let lines = table.lines().collect::<Vec<_>>();
+        assert_eq!(4, lines.len(), "{table}");
+        assert!(lines[0].starts_with("DOMAIN           PATH  NAME     VALUE  SECURE  HTTPONLY"));
+        assert!(lines[1].starts_with("example.com      /     shared   2      no      no        -"));
+        assert!(lines[3].starts_with("www.example.com  /     session  1      yes     yes       -"));
+        assert!(lines[3].ends_with("session"));
+        assert_eq!(
+            6,
+            run_to_string(&format!("{jar} list --expired"))
+                .lines()
+                .count()
+        );
+
+        let header = run_to_string(&format!("{jar} header https://www.example.com/"));
+        let mut pairs = header.trim_end().split("; ").collect::<Vec<_>>();
+        pairs.sort_unstable();
+        assert_eq!(vec!["pref=dark", "session=1", "shared=2"], pairs);
+        assert_eq!(
+            "",
+            run_to_string(&format!("{jar} header https://nowhere.test/"))
+        );
+
+        assert_eq!("", run_to_string(&format!("{jar} purge")));
+        assert_eq!(
+            5,
+            run_to_string(&format!("{jar} list --expired"))
+                .lines()
+                .count()
+        );
+        run_to_string(&format!("{jar} delete example.com --subdomains"));
+        assert_eq!(2, run_to_string(&format!("{jar} list")).lines().count());
+
+        let ron = temp_path("jar.ron");
+        let ron_str = ron.to_str().unwrap();
+        run_to_string(&format!("{jar} convert --to ron -o {ron_str}"));
+        assert!(run_to_string(&format!("{ron_str} list")).contains("other.test"));
+        let legacy = run_to_string(&format!("{ron_str} convert --to legacy"));
+        assert!(legacy.contains("other=3"));
+        std::fs::remove_file(jar).unwrap();
+        std::fs::remove_file(ron).unwrap();
+    }
+}
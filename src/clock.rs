@@ -0,0 +1,114 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use time::{Duration, OffsetDateTime};
+
+/// A source of the current time, consulted by a [`CookieStore`](crate::CookieStore) when
+/// computing expiry times from Max-Age attributes and deciding whether cookies have expired.
+/// Install a clock via [`CookieStore::with_clock`](crate::CookieStore::with_clock); by default
+/// the [`SystemClock`] is used.
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// The current time, in UTC
+    fn now_utc(&self) -> OffsetDateTime;
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now_utc(&self) -> OffsetDateTime {
+        (**self).now_utc()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Box<C> {
+    fn now_utc(&self) -> OffsetDateTime {
+        (**self).now_utc()
+    }
+}
+
+/// The system clock, i.e. [`OffsetDateTime::now_utc`]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_utc(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc()
+    }
+}
+
+/// A clock which reports a fixed time until explicitly [set](ManualClock::set) or
+/// [advanced](ManualClock::advance), for deterministic handling of expiration (e.g. in tests).
+/// Clones of a `ManualClock` share the same time, so a clone may be retained to control a clock
+/// installed in a `CookieStore`.
+///
+/// ```
+/// # use cookie_store::{CookieStore, ManualClock};
+/// let clock = ManualClock::new(time::OffsetDateTime::UNIX_EPOCH);
+/// let mut store = CookieStore::default().with_clock(clock.clone());
+/// let url = url::Url::parse("https://example.com/").unwrap();
+/// store.parse("cookie1=value1; Max-Age=60", &url).unwrap();
+/// assert_eq!(1, store.matches(&url).len());
+/// clock.advance(time::Duration::minutes(1));
+/// assert_eq!(0, store.matches(&url).len());
+/// ```
+#[derive(Clone)]
+pub struct ManualClock(Arc<Mutex<OffsetDateTime>>);
+
+impl ManualClock {
+    pub fn new(utc_tm: OffsetDateTime) -> ManualClock {
+        ManualClock(Arc::new(Mutex::new(utc_tm)))
+    }
+
+    /// Set the time reported by this clock to `utc_tm`
+    pub fn set(&self, utc_tm: OffsetDateTime) {
+        *self.lock() = utc_tm;
+    }
+
+    /// Advance the time reported by this clock by `duration`
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.lock();
+        *now += duration;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, OffsetDateTime> {
+        // the guarded value is always valid, so a poisoned lock may be safely recovered
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> ManualClock {
+        ManualClock::new(OffsetDateTime::now_utc())
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_utc(&self) -> OffsetDateTime {
+        *self.lock()
+    }
+}
+
+impl fmt::Debug for ManualClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ManualClock").field(&*self.lock()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{Duration, OffsetDateTime};
+
+    use super::{Clock, ManualClock};
+
+    #[test]
+    fn manual_clock() {
+        let clock = ManualClock::new(OffsetDateTime::UNIX_EPOCH);
+        let shared = clock.clone();
+        assert_eq!(OffsetDateTime::UNIX_EPOCH, clock.now_utc());
+        shared.advance(Duration::days(1));
+        assert_eq!(
+            OffsetDateTime::UNIX_EPOCH + Duration::days(1),
+            clock.now_utc()
+        );
+        shared.set(OffsetDateTime::UNIX_EPOCH);
+        assert_eq!(OffsetDateTime::UNIX_EPOCH, clock.now_utc());
+    }
+}
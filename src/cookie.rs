@@ -1,6 +1,7 @@
 use crate::cookie_domain::CookieDomain;
 use crate::cookie_expiration::CookieExpiration;
 use crate::cookie_path::CookiePath;
+use crate::cookie_priority::CookiePriority;
 
 use crate::utils::{is_http_scheme, is_secure};
 use cookie::{Cookie as RawCookie, CookieBuilder as RawCookieBuilder, ParseError};
@@ -10,10 +11,14 @@ use std::borrow::Cow;
 use std::convert::TryFrom;
 use std::fmt;
 use std::ops::Deref;
+use std::sync::Arc;
+use time::OffsetDateTime;
 use url::Url;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum Error {
+/// The kind of an [`Error`], for matching on the reason a cookie was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
     /// Cookie had attribute HttpOnly but was received from a request-uri which was not an http
     /// scheme
     NonHttpScheme,
@@ -32,43 +37,189 @@ pub enum Error {
     PublicSuffix,
     /// Tried to use a CookieDomain variant of `Empty` or `NotPresent` in a context requiring a Domain value
     UnspecifiedDomain,
+    /// Cookie was rejected by the `CookiePolicy` of the `CookieStore`
+    PolicyRejected,
+    /// Cookie is not same-site with the top-level site, and third-party cookies are blocked
+    ThirdParty,
 }
 
-impl std::error::Error for Error {}
-
-impl fmt::Display for Error {
+impl fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
             "{}",
             match *self {
-                Error::NonHttpScheme =>
+                ErrorKind::NonHttpScheme =>
                     "request-uri is not an http scheme but HttpOnly attribute set",
-                Error::NonRelativeScheme => {
+                ErrorKind::NonRelativeScheme => {
                     "request-uri is not a relative scheme; cannot determine host"
                 }
-                Error::DomainMismatch => "request-uri does not domain-match the cookie",
-                Error::Expired => "attempted to utilize an Expired Cookie",
-                Error::Parse => "unable to parse string as cookie::Cookie",
+                ErrorKind::DomainMismatch => "request-uri does not domain-match the cookie",
+                ErrorKind::Expired => "attempted to utilize an Expired Cookie",
+                ErrorKind::Parse => "unable to parse string as cookie::Cookie",
                 #[cfg(feature = "public_suffix")]
-                Error::PublicSuffix => "domain-attribute value is a public suffix",
-                Error::UnspecifiedDomain => "domain-attribute is not specified",
+                ErrorKind::PublicSuffix => "domain-attribute value is a public suffix",
+                ErrorKind::UnspecifiedDomain => "domain-attribute is not specified",
+                ErrorKind::PolicyRejected => "cookie rejected by the cookie policy",
+                ErrorKind::ThirdParty => "third-party cookie blocked for the top-level site",
             }
         )
     }
 }
 
-// cookie::Cookie::parse returns Result<Cookie, ()>
+/// An error creating or storing a `Cookie`: its [`ErrorKind`], along with whatever is known of
+/// the offending cookie and the request-uri it was received from
+#[derive(Debug, Clone)]
+pub struct Error {
+    kind: ErrorKind,
+    name: Option<String>,
+    domain: Option<String>,
+    path: Option<String>,
+    request_host: Option<String>,
+    source: Option<Arc<dyn std::error::Error + Send + Sync>>,
+}
+
+impl Error {
+    /// The kind of error, e.g. the reason the cookie was rejected
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The name of the offending cookie, if known
+    pub fn cookie_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The domain of the offending cookie, if known: its domain-attribute, or the request-host
+    /// for a host-only cookie
+    pub fn domain(&self) -> Option<&str> {
+        self.domain.as_deref()
+    }
+
+    /// The path of the offending cookie, if known
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    /// The host of the request-uri the offending cookie was received from, if known
+    pub fn request_host(&self) -> Option<&str> {
+        self.request_host.as_deref()
+    }
+
+    /// Add the name, domain, and path of `raw_cookie`, where not already known
+    pub(crate) fn with_raw_cookie(mut self, raw_cookie: &RawCookie<'_>) -> Error {
+        self.name = self.name.or_else(|| Some(raw_cookie.name().to_owned()));
+        self.domain = self
+            .domain
+            .or_else(|| raw_cookie.domain().map(str::to_owned));
+        self.path = self.path.or_else(|| raw_cookie.path().map(str::to_owned));
+        self
+    }
+
+    /// Add the name, domain, and path of `cookie`, where not already known
+    pub(crate) fn with_cookie(mut self, cookie: &Cookie<'_>) -> Error {
+        self.name = self.name.or_else(|| Some(cookie.name().to_owned()));
+        self.domain = self
+            .domain
+            .or_else(|| cookie.domain.as_cow().map(Cow::into_owned));
+        self.path = self.path.or_else(|| Some(String::from(&cookie.path)));
+        self
+    }
+
+    /// Add the host of `request_url`, where not already known
+    pub(crate) fn with_request_url(mut self, request_url: &Url) -> Error {
+        self.request_host = self
+            .request_host
+            .or_else(|| request_url.host_str().map(str::to_owned));
+        self
+    }
+
+    pub(crate) fn with_source(mut self, source: crate::Error) -> Error {
+        self.source = Some(Arc::from(source));
+        self
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Error {
+        Error {
+            kind,
+            name: None,
+            domain: None,
+            path: None,
+            request_host: None,
+            source: None,
+        }
+    }
+}
+
+/// Errors are equal if their kinds and context are equal, and their sources (if any) display
+/// identically
+impl PartialEq for Error {
+    fn eq(&self, other: &Error) -> bool {
+        self.kind == other.kind
+            && self.name == other.name
+            && self.domain == other.domain
+            && self.path == other.path
+            && self.request_host == other.request_host
+            && self.source.as_ref().map(ToString::to_string)
+                == other.source.as_ref().map(ToString::to_string)
+    }
+}
+
+impl Eq for Error {}
+
+impl PartialEq<ErrorKind> for Error {
+    fn eq(&self, kind: &ErrorKind) -> bool {
+        self.kind == *kind
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| source.as_ref() as &(dyn std::error::Error + 'static))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        let context = [
+            ("cookie", &self.name),
+            ("domain", &self.domain),
+            ("path", &self.path),
+            ("request-host", &self.request_host),
+        ];
+        let mut context = context
+            .iter()
+            .filter_map(|(label, value)| value.as_ref().map(|value| (label, value)))
+            .peekable();
+        if context.peek().is_some() {
+            write!(f, " (")?;
+            for (i, (label, value)) in context.enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{label} '{value}'")?;
+            }
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
 impl From<ParseError> for Error {
-    fn from(_: ParseError) -> Error {
-        Error::Parse
+    fn from(e: ParseError) -> Error {
+        Error::from(ErrorKind::Parse).with_source(e.into())
     }
 }
 
 pub type CookieResult<'a> = Result<Cookie<'a>, Error>;
 
 /// A cookie conforming more closely to [IETF RFC6265](https://datatracker.ietf.org/doc/html/rfc6265)
-#[derive(PartialEq, Clone, Debug)]
+#[derive(Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Cookie<'a> {
     /// The parsed Set-Cookie data
@@ -96,8 +247,70 @@ pub struct Cookie<'a> {
     /// indicating a non-persistent `Cookie` that should expire at the end of the
     /// session
     pub expires: CookieExpiration,
+    /// When the `Cookie` was first stored, per [IETF RFC6265 Section
+    /// 5.3](https://datatracker.ietf.org/doc/html/rfc6265#section-5.3); set when the `Cookie` is
+    /// inserted into a [`CookieStore`](crate::CookieStore), and retained when it is subsequently
+    /// updated. `None` for a `Cookie` which has not been stored.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            with = "crate::rfc3339_fmt::option"
+        )
+    )]
+    creation_time: Option<OffsetDateTime>,
+    /// The Priority attribute from a Set-Cookie header, or `Medium` if there is no valid Priority
+    /// attribute
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "CookiePriority::is_default")
+    )]
+    priority: CookiePriority,
+    /// The Set-Cookie header value the `Cookie` was parsed from, if any
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    set_cookie: Option<String>,
+    /// The attributes of the Set-Cookie header not recognized by `cookie::Cookie`, in order
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    extension_attributes: Vec<(String, Option<String>)>,
 }
 
+/// Cookies are equal if their attributes are equal; the Set-Cookie header text they were parsed
+/// from, if any, is not compared. As it is persisted to the second, the creation time is compared
+/// to the second.
+impl<'a, 'b> PartialEq<Cookie<'b>> for Cookie<'a> {
+    fn eq(&self, other: &Cookie<'b>) -> bool {
+        self.raw_cookie == other.raw_cookie
+            && self.path == other.path
+            && self.domain == other.domain
+            && self.expires == other.expires
+            && self.creation_time.map(OffsetDateTime::unix_timestamp)
+                == other.creation_time.map(OffsetDateTime::unix_timestamp)
+            && self.priority == other.priority
+            && self.extension_attributes == other.extension_attributes
+    }
+}
+
+/// The (lowercase) names of the attributes recognized by `cookie::Cookie`, and of Priority, which
+/// is held by `Cookie::priority`
+const RECOGNIZED_ATTRIBUTES: [&str; 9] = [
+    "expires",
+    "max-age",
+    "domain",
+    "path",
+    "secure",
+    "httponly",
+    "samesite",
+    "partitioned",
+    "priority",
+];
+
 #[cfg(feature = "serde")]
 mod serde_raw_cookie {
     use cookie::Cookie as RawCookie;
@@ -137,6 +350,41 @@ impl<'a> Cookie<'a> {
             && (!self.raw_cookie.http_only().unwrap_or(false) || is_http_scheme(request_url))
     }
 
+    /// When the `Cookie` was first stored, per [IETF RFC6265 Section
+    /// 5.3](https://datatracker.ietf.org/doc/html/rfc6265#section-5.3); `None` for a `Cookie` which
+    /// has not been stored
+    pub fn creation_time(&self) -> Option<OffsetDateTime> {
+        self.creation_time
+    }
+
+    pub(crate) fn set_creation_time(&mut self, creation_time: OffsetDateTime) {
+        self.creation_time = Some(creation_time);
+    }
+
+    /// The Set-Cookie header value the `Cookie` was parsed from, exactly as received; `None` if
+    /// the `Cookie` was created from a `cookie::Cookie`, e.g. via
+    /// [`Cookie::try_from_raw_cookie`]
+    pub fn set_cookie_header(&self) -> Option<&str> {
+        self.set_cookie.as_deref()
+    }
+
+    /// The Priority attribute of the Set-Cookie header the `Cookie` was parsed from, or `Medium`
+    /// if there is no valid Priority attribute. As `cookie::Cookie` does not retain the attribute,
+    /// a `Cookie` created via [`Cookie::try_from_raw_cookie`] (e.g. by
+    /// [`CookieStore::store_response_cookies`](crate::CookieStore::store_response_cookies)) has
+    /// `Medium` priority; see
+    /// [`CookieStore::store_response_set_cookie_headers`](crate::CookieStore::store_response_set_cookie_headers).
+    pub fn priority(&self) -> CookiePriority {
+        self.priority
+    }
+
+    /// The attributes of the Set-Cookie header the `Cookie` was parsed from which are not
+    /// otherwise recognized (e.g. vendor-specific attributes), as (name, value) pairs in the
+    /// order received
+    pub fn extension_attributes(&self) -> &[(String, Option<String>)] {
+        &self.extension_attributes
+    }
+
     /// Should this `Cookie` be persisted across sessions?
     pub fn is_persistent(&self) -> bool {
         match self.expires {
@@ -148,9 +396,13 @@ impl<'a> Cookie<'a> {
     /// Expire this cookie
     pub fn expire(&mut self) {
         self.expires = CookieExpiration::from(0u64);
+        // a received Max-Age no longer describes the expiry
+        self.raw_cookie.set_max_age(None);
     }
 
-    /// Return whether the `Cookie` is expired *now*
+    /// Return whether the `Cookie` is expired *now*, per the system clock; to consult the
+    /// [`Clock`](crate::Clock) of a store, use
+    /// [`expires_by`](Cookie::expires_by)`(&store.now())`.
     pub fn is_expired(&self) -> bool {
         self.expires.is_expired()
     }
@@ -165,17 +417,84 @@ impl<'a> Cookie<'a> {
     where
         S: Into<Cow<'a, str>>,
     {
-        Cookie::try_from_raw_cookie(&RawCookie::parse(cookie_str)?, request_url)
+        Cookie::parse_at(cookie_str, request_url, OffsetDateTime::now_utc())
+    }
+
+    /// Parses a new `cookie_store::Cookie` from `cookie_str`, received at `now_utc`; any Max-Age
+    /// attribute is taken relative to `now_utc`.
+    ///
+    /// An Expires attribute in a format not understood by `cookie::Cookie::parse` is parsed per
+    /// the lenient algorithm of [RFC6265 Section
+    /// 5.1.1](https://datatracker.ietf.org/doc/html/rfc6265#section-5.1.1); see
+    /// [`CookieExpiration::from_cookie_date`]. As a `cookie::Cookie` does not retain the text it
+    /// was parsed from, this fallback is not available to [`Cookie::try_from_raw_cookie`].
+    pub fn parse_at<S>(
+        cookie_str: S,
+        request_url: &Url,
+        now_utc: OffsetDateTime,
+    ) -> CookieResult<'a>
+    where
+        S: Into<Cow<'a, str>>,
+    {
+        let cookie_str = cookie_str.into();
+        let set_cookie = cookie_str.to_string();
+        let mut raw_cookie = RawCookie::parse(cookie_str)
+            .map_err(|e| Error::from(e).with_request_url(request_url))?;
+        if raw_cookie.max_age().is_none() && raw_cookie.expires().is_none() {
+            if let Some(utc_tm) = crate::cookie_date::expires_attribute(&set_cookie)
+                .and_then(crate::cookie_date::parse)
+            {
+                raw_cookie.set_expires(utc_tm);
+            }
+        }
+        let mut cookie = Cookie::try_from_raw_cookie_at(&raw_cookie, request_url, now_utc)?;
+        cookie.extension_attributes = crate::utils::attributes(&set_cookie)
+            .filter(|(name, _)| {
+                !RECOGNIZED_ATTRIBUTES
+                    .iter()
+                    .any(|recognized| name.eq_ignore_ascii_case(recognized))
+            })
+            .map(|(name, value)| (name.to_owned(), value.map(str::to_owned)))
+            .collect();
+        cookie.priority = crate::utils::attributes(&set_cookie)
+            .filter(|(name, _)| name.eq_ignore_ascii_case("priority"))
+            .filter_map(|(_, value)| value.and_then(CookiePriority::from_attribute))
+            .last()
+            .unwrap_or_default();
+        cookie.set_cookie = Some(set_cookie);
+        Ok(cookie)
+    }
+
+    /// A [`CookieBuilder`](crate::CookieBuilder) for a `Cookie` named `name` with value `value`,
+    /// for creating a `Cookie` directly rather than from a Set-Cookie header
+    pub fn builder<N, V>(name: N, value: V) -> crate::CookieBuilder
+    where
+        N: Into<String>,
+        V: Into<String>,
+    {
+        crate::CookieBuilder::new(name, value)
     }
 
     /// Create a new `cookie_store::Cookie` from a `cookie::Cookie` (from the `cookie` crate)
     /// received from `request_url`.
     pub fn try_from_raw_cookie(raw_cookie: &RawCookie<'a>, request_url: &Url) -> CookieResult<'a> {
+        Cookie::try_from_raw_cookie_at(raw_cookie, request_url, OffsetDateTime::now_utc())
+    }
+
+    /// Create a new `cookie_store::Cookie` from a `cookie::Cookie` (from the `cookie` crate)
+    /// received from `request_url` at `now_utc`; any Max-Age attribute is taken relative to
+    /// `now_utc`.
+    pub fn try_from_raw_cookie_at(
+        raw_cookie: &RawCookie<'a>,
+        request_url: &Url,
+        now_utc: OffsetDateTime,
+    ) -> CookieResult<'a> {
+        let error = |e: Error| e.with_raw_cookie(raw_cookie).with_request_url(request_url);
         if raw_cookie.http_only().unwrap_or(false) && !is_http_scheme(request_url) {
             // If the cookie was received from a "non-HTTP" API and the
             // cookie's http-only-flag is set, abort these steps and ignore the
             // cookie entirely.
-            return Err(Error::NonHttpScheme);
+            return Err(error(ErrorKind::NonHttpScheme.into()));
         }
 
         let domain = match CookieDomain::try_from(raw_cookie) {
@@ -185,7 +504,7 @@ impl<'a> Cookie<'a> {
                     //    If the canonicalized request-host does not domain-match the
                     //    domain-attribute:
                     //       Ignore the cookie entirely and abort these steps.
-                    Err(Error::DomainMismatch)
+                    Err(error(ErrorKind::DomainMismatch.into()))
                 } else {
                     //    Otherwise:
                     //       Set the cookie's host-only-flag to false.
@@ -193,11 +512,11 @@ impl<'a> Cookie<'a> {
                     Ok(d)
                 }
             }
-            Err(_) => Err(Error::Parse),
+            Err(e) => Err(error(Error::from(ErrorKind::Parse).with_source(e))),
             // Otherwise:
             //    Set the cookie's host-only-flag to true.
             //    Set the cookie's domain to the canonicalized request-host.
-            _ => CookieDomain::host_only(request_url),
+            _ => CookieDomain::host_only(request_url).map_err(error),
         }?;
 
         let path = raw_cookie
@@ -209,7 +528,7 @@ impl<'a> Cookie<'a> {
         // per RFC6265, Max-Age takes precedence, then Expires, otherwise is Session
         // only
         let expires = if let Some(max_age) = raw_cookie.max_age() {
-            CookieExpiration::from(max_age)
+            CookieExpiration::from_max_age_at(max_age, now_utc)
         } else if let Some(expiration) = raw_cookie.expires() {
             CookieExpiration::from(expiration)
         } else {
@@ -221,15 +540,44 @@ impl<'a> Cookie<'a> {
             path,
             expires,
             domain,
+            creation_time: None,
+            priority: CookiePriority::default(),
+            set_cookie: None,
+            extension_attributes: Vec::new(),
         })
     }
 
+    /// Assemble a `Cookie` not parsed from a Set-Cookie header; the caller is responsible for
+    /// `raw_cookie` agreeing with the other parts
+    pub(crate) fn from_parts(
+        raw_cookie: RawCookie<'a>,
+        path: CookiePath,
+        domain: CookieDomain,
+        expires: CookieExpiration,
+        priority: CookiePriority,
+    ) -> Cookie<'a> {
+        Cookie {
+            raw_cookie,
+            path,
+            expires,
+            domain,
+            creation_time: None,
+            priority,
+            set_cookie: None,
+            extension_attributes: Vec::new(),
+        }
+    }
+
     pub fn into_owned(self) -> Cookie<'static> {
         Cookie {
             raw_cookie: self.raw_cookie.into_owned(),
             path: self.path,
             domain: self.domain,
             expires: self.expires,
+            creation_time: self.creation_time,
+            priority: self.priority,
+            set_cookie: self.set_cookie,
+            extension_attributes: self.extension_attributes,
         }
     }
 }
@@ -241,17 +589,22 @@ impl<'a> Deref for Cookie<'a> {
     }
 }
 
+/// Reconstructs the cookie from its name, value, expiry, path, and domain. A Max-Age attribute
+/// is kept as received, along with any Expires attribute; as Max-Age is relative to when the
+/// cookie is received, use the `expires` field for the absolute expiry. Extension attributes are
+/// dropped; the original text is available from [`Cookie::set_cookie_header`].
 impl<'a> From<Cookie<'a>> for RawCookie<'a> {
     fn from(cookie: Cookie<'a>) -> RawCookie<'static> {
         let mut builder =
             RawCookieBuilder::new(cookie.name().to_owned(), cookie.value().to_owned());
 
-        // Max-Age is relative, will not have same meaning now, so only set `Expires`.
-        match cookie.expires {
-            CookieExpiration::AtUtc(utc_tm) => {
+        if let Some(max_age) = cookie.raw_cookie.max_age() {
+            builder = builder.max_age(max_age);
+            if let Some(utc_tm) = cookie.raw_cookie.expires_datetime() {
                 builder = builder.expires(utc_tm);
             }
-            CookieExpiration::SessionEnd => {}
+        } else if let CookieExpiration::AtUtc(utc_tm) = cookie.expires {
+            builder = builder.expires(utc_tm);
         }
 
         if cookie.path.is_from_path_attr() {
@@ -271,6 +624,7 @@ mod tests {
     use super::Cookie;
     use crate::cookie_domain::CookieDomain;
     use crate::cookie_expiration::CookieExpiration;
+    use crate::cookie_priority::CookiePriority;
     use cookie::Cookie as RawCookie;
     use time::{Duration, OffsetDateTime};
     use url::Url;
@@ -339,6 +693,41 @@ mod tests {
         domain_from("baz.foo.example.com", "http://foo.example.com", false);
     }
 
+    #[test]
+    fn error_context() {
+        use super::ErrorKind;
+        use std::error::Error as _;
+
+        let url = test_utils::url("http://www.example.com/foo/bar");
+        let e = Cookie::parse("cookie1=value1; Domain=other.com; Path=/", &url).unwrap_err();
+        assert_eq!(ErrorKind::DomainMismatch, e.kind());
+        assert!(e == ErrorKind::DomainMismatch);
+        assert_eq!(Some("cookie1"), e.cookie_name());
+        assert_eq!(Some("other.com"), e.domain());
+        assert_eq!(Some("/"), e.path());
+        assert_eq!(Some("www.example.com"), e.request_host());
+        assert!(e.source().is_none());
+        assert_eq!(
+            "request-uri does not domain-match the cookie (cookie 'cookie1', domain 'other.com', \
+             path '/', request-host 'www.example.com')",
+            e.to_string()
+        );
+
+        let e = Cookie::parse("=value1", &url).unwrap_err();
+        assert_eq!(ErrorKind::Parse, e.kind());
+        assert_eq!(None, e.cookie_name());
+        assert_eq!(Some("www.example.com"), e.request_host());
+        assert_eq!(
+            Some(cookie::ParseError::EmptyName.as_str().to_owned()),
+            e.source().map(|e| e.to_string())
+        );
+
+        let e = Cookie::parse("cookie1=value1", &test_utils::url("data:text/plain,a")).unwrap_err();
+        assert_eq!(ErrorKind::NonRelativeScheme, e.kind());
+        assert_eq!(Some("cookie1"), e.cookie_name());
+        assert_eq!(None, e.request_host());
+    }
+
     #[test]
     fn httponly() {
         let c = RawCookie::parse("cookie1=value1; HttpOnly").unwrap();
@@ -581,6 +970,108 @@ mod tests {
         assert!(ua.expires_by(&in_minutes(2)));
     }
 
+    #[test]
+    fn lenient_expires() {
+        let url = test_utils::url("http://example.com/foo/bar");
+        for cookie_str in [
+            "cookie1=value1; Expires=Wed, 09-Jun-2021 10:18:14 GMT",
+            "cookie1=value1; expires=09 June 21 10:18:14",
+            "cookie1=value1; Expires=Wed Jun 9 10:18:14 2021; Path=/",
+        ] {
+            let ua = Cookie::parse(cookie_str, &url).unwrap();
+            assert_eq!(
+                CookieExpiration::AtUtc(time::macros::datetime!(2021-06-09 10:18:14 UTC)),
+                ua.expires,
+                "{cookie_str}"
+            );
+        }
+        // Max-Age still takes precedence
+        let ua = Cookie::parse(
+            "cookie1=value1; Max-Age=60; Expires=09 June 21 10:18:14",
+            &url,
+        )
+        .unwrap();
+        assert!(!ua.is_expired());
+        // an invalid cookie-date is ignored
+        let ua = Cookie::parse("cookie1=value1; Expires=09 June 21", &url).unwrap();
+        assert_eq!(CookieExpiration::SessionEnd, ua.expires);
+    }
+
+    #[test]
+    fn set_cookie_text() {
+        let url = test_utils::url("http://example.com/foo/bar");
+        let set_cookie =
+            "cookie1=value1; Max-Age=60; Priority=Low; SameSite=Lax; x-Vendor = a=b; Flag";
+        let ua = Cookie::parse(set_cookie, &url).unwrap();
+        assert_eq!(Some(set_cookie), ua.set_cookie_header());
+        assert_eq!(
+            &[
+                ("x-Vendor".to_owned(), Some("a=b".to_owned())),
+                ("Flag".to_owned(), None),
+            ][..],
+            ua.extension_attributes()
+        );
+        let ua = ua.into_owned();
+        assert_eq!(Some(set_cookie), ua.set_cookie_header());
+        assert_eq!(2, ua.extension_attributes().len());
+        assert_eq!(CookiePriority::Low, ua.priority());
+
+        let ua = Cookie::try_from_raw_cookie(&RawCookie::parse(set_cookie).unwrap(), &url).unwrap();
+        assert_eq!(None, ua.set_cookie_header());
+        assert!(ua.extension_attributes().is_empty());
+        assert_eq!(CookiePriority::Medium, ua.priority());
+    }
+
+    #[test]
+    fn into_raw_cookie() {
+        let url = test_utils::url("http://example.com/foo/bar");
+        let ua = Cookie::parse("cookie1=value1; Max-Age=60; Path=/", &url).unwrap();
+        assert_eq!(
+            "cookie1=value1; Path=/; Max-Age=60",
+            RawCookie::from(ua.clone()).to_string()
+        );
+        let mut expired = ua;
+        expired.expire();
+        let raw_cookie = RawCookie::from(expired);
+        assert_eq!(None, raw_cookie.max_age());
+        assert_eq!(
+            Some(OffsetDateTime::UNIX_EPOCH),
+            raw_cookie.expires_datetime()
+        );
+
+        let ua = Cookie::parse(
+            "cookie1=value1; Expires=Tue, 11 Aug 2015 16:41:42 GMT",
+            &url,
+        )
+        .unwrap();
+        assert_eq!(
+            "cookie1=value1; Expires=Tue, 11 Aug 2015 16:41:42 GMT",
+            RawCookie::from(ua).to_string()
+        );
+    }
+
+    #[test]
+    fn priority() {
+        let url = test_utils::url("http://example.com/foo/bar");
+        for (cookie_str, expected) in [
+            ("cookie1=value1", CookiePriority::Medium),
+            ("cookie1=value1; Priority=Low", CookiePriority::Low),
+            (
+                "cookie1=value1; priority=HIGH; Path=/",
+                CookiePriority::High,
+            ),
+            (
+                "cookie1=value1; Priority=Low; Priority=High",
+                CookiePriority::High,
+            ),
+            ("cookie1=value1; Priority=Urgent", CookiePriority::Medium),
+            ("cookie1=value1; Priority", CookiePriority::Medium),
+        ] {
+            let ua = Cookie::parse(cookie_str, &url).unwrap();
+            assert_eq!(expected, ua.priority(), "{cookie_str}");
+        }
+    }
+
     // A request-path path-matches a given cookie-path if at least one of
     // the following conditions holds:
     // o  The cookie-path and the request-path are identical.
@@ -733,7 +1224,11 @@ mod serde_json_tests {
     use crate::utils::test::*;
     use serde_json::json;
 
-    fn encode_decode(c: &Cookie<'_>, expected: serde_json::Value) {
+    fn encode_decode(c: &Cookie<'_>, mut expected: serde_json::Value) {
+        // the Set-Cookie header, if any, is persisted verbatim
+        if let Some(set_cookie) = c.set_cookie_header() {
+            expected["set_cookie"] = set_cookie.into();
+        }
         let encoded = serde_json::to_value(c).unwrap();
         assert_eq!(
             expected, encoded,
@@ -881,4 +1376,46 @@ mod serde_json_tests {
             }),
         );
     }
+
+    #[test]
+    fn serde_extension_attributes() {
+        encode_decode(
+            &test_utils::make_cookie(
+                "cookie8=value8; Priority=High; Path=/; x-vendor",
+                "http://example.com/foo/bar",
+                None,
+                None,
+            ),
+            json!({
+                "raw_cookie": "cookie8=value8; Path=/",
+                "path": ["/", true],
+                "domain": { "HostOnly": "example.com" },
+                "expires": "SessionEnd",
+                "priority": "High",
+                "extension_attributes": [["x-vendor", null]],
+            }),
+        );
+    }
+
+    #[test]
+    fn serde_set_cookie() {
+        let set_cookie = "cookie9=value9; Path=/";
+        let cookie = test_utils::make_cookie(set_cookie, "http://example.com/foo/bar", None, None);
+        let encoded = serde_json::to_value(&cookie).unwrap();
+        assert_eq!(Some(&json!(set_cookie)), encoded.get("set_cookie"));
+
+        let decoded: Cookie<'_> = serde_json::from_value(json!({
+            "raw_cookie": "cookie9=value9; Path=/",
+            "path": ["/", true],
+            "domain": { "HostOnly": "example.com" },
+            "expires": "SessionEnd",
+            "set_cookie": set_cookie,
+        }))
+        .unwrap();
+        assert_eq!(Some(set_cookie), decoded.set_cookie_header());
+        let decoded: Cookie<'_> = serde_json::from_value(encoded).unwrap();
+        assert_eq!(Some(set_cookie), decoded.set_cookie_header());
+        // the header text is not compared
+        assert_eq!(cookie, decoded);
+    }
 }
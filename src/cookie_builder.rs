@@ -0,0 +1,384 @@
+use cookie::{CookieBuilder as RawCookieBuilder, SameSite};
+
+use crate::cookie::{Cookie, Error as CookieError, ErrorKind as CookieErrorKind};
+use crate::cookie_domain::CookieDomain;
+use crate::cookie_expiration::CookieExpiration;
+use crate::cookie_path::CookiePath;
+use crate::cookie_priority::CookiePriority;
+use crate::utils::is_host_name;
+use crate::IdnaErrors;
+
+/// Builds a [`Cookie`] from its parts, without parsing a Set-Cookie header or inferring the
+/// domain and path from a request-uri; e.g. for test fixtures, or when importing cookies from
+/// another source.
+///
+/// ```
+/// use cookie_store::{Cookie, CookieDomain, CookieExpiration, SameSite};
+///
+/// let cookie = Cookie::builder("session", "abc123")
+///     .with_domain(CookieDomain::Suffix("example.com".to_owned()))
+///     .with_expires(CookieExpiration::from(3600u64))
+///     .with_secure(true)
+///     .with_same_site(SameSite::Lax)
+///     .build()
+///     .unwrap();
+/// assert!(cookie.matches(&url::Url::parse("https://www.example.com/").unwrap()));
+/// ```
+#[derive(Clone, Debug)]
+pub struct CookieBuilder {
+    name: String,
+    value: String,
+    domain: CookieDomain,
+    path: CookiePath,
+    expires: CookieExpiration,
+    secure: Option<bool>,
+    http_only: Option<bool>,
+    same_site: Option<SameSite>,
+    priority: CookiePriority,
+}
+
+impl CookieBuilder {
+    /// A builder for a `Cookie` named `name` with value `value`. Unless otherwise set, the
+    /// `Cookie` has path "/", expires at the end of the session, and has no Secure, HttpOnly or
+    /// SameSite attributes; a domain must be set with [`CookieBuilder::with_domain`].
+    pub fn new<N, V>(name: N, value: V) -> CookieBuilder
+    where
+        N: Into<String>,
+        V: Into<String>,
+    {
+        CookieBuilder {
+            name: name.into(),
+            value: value.into(),
+            domain: CookieDomain::NotPresent,
+            path: CookiePath::root(),
+            expires: CookieExpiration::SessionEnd,
+            secure: None,
+            http_only: None,
+            same_site: None,
+            priority: CookiePriority::default(),
+        }
+    }
+
+    /// Set the domain: `CookieDomain::HostOnly` for a cookie sent only to that host, or
+    /// `CookieDomain::Suffix` for one sent to that domain and its subdomains
+    pub fn with_domain(mut self, domain: CookieDomain) -> CookieBuilder {
+        self.domain = domain;
+        self
+    }
+
+    /// Set the path: the cookie is sent for request paths which path-match it, e.g. `/app` for
+    /// `/app` and `/app/page`; see [`CookiePath::parse`]
+    pub fn with_path(mut self, path: CookiePath) -> CookieBuilder {
+        self.path = path;
+        self
+    }
+
+    /// Set the expiry: `CookieExpiration::AtUtc` for a persistent cookie, or
+    /// `CookieExpiration::SessionEnd` for one which expires at the end of the session
+    pub fn with_expires(mut self, expires: CookieExpiration) -> CookieBuilder {
+        self.expires = expires;
+        self
+    }
+
+    /// Set the Secure attribute: if `true`, the cookie is sent only over secure connections,
+    /// e.g. https
+    pub fn with_secure(mut self, secure: bool) -> CookieBuilder {
+        self.secure = Some(secure);
+        self
+    }
+
+    /// Set the HttpOnly attribute: if `true`, the cookie is sent only to http(s) request-uris,
+    /// and may not be replaced by a cookie from a non-HTTP API
+    pub fn with_http_only(mut self, http_only: bool) -> CookieBuilder {
+        self.http_only = Some(http_only);
+        self
+    }
+
+    /// Set the SameSite attribute, which browsers consult when deciding whether to send the
+    /// cookie with cross-site requests; it is retained, but not enforced, by a `CookieStore`
+    pub fn with_same_site(mut self, same_site: SameSite) -> CookieBuilder {
+        self.same_site = Some(same_site);
+        self
+    }
+
+    /// Set the priority: when cookies must be evicted to make room, those of lower priority are
+    /// evicted first; see [`CookieStore::with_max_cookies_per_domain`](crate::CookieStore::with_max_cookies_per_domain)
+    pub fn with_priority(mut self, priority: CookiePriority) -> CookieBuilder {
+        self.priority = priority;
+        self
+    }
+
+    /// Build the `Cookie`. Fails with:
+    /// * [`CookieErrorKind::UnspecifiedDomain`] if no domain was set, or it is empty
+    /// * [`CookieErrorKind::Parse`] if the name is empty or contains whitespace, control
+    ///   characters, `;` or `=`; if the value contains control characters or `;`; if the domain
+    ///   is not a valid internationalized domain name; if a `CookieDomain::Suffix` is an IP
+    ///   address; or if the name has a `__Secure-` prefix and the cookie is not Secure, or a
+    ///   `__Host-` prefix and the cookie is not Secure, host-only, with path `/` (prefixes are
+    ///   matched case-insensitively)
+    ///
+    /// The domain is canonicalized, e.g. lowercased and punycode-encoded.
+    pub fn build(self) -> Result<Cookie<'static>, CookieError> {
+        let mut raw_cookie = RawCookieBuilder::new(self.name, self.value);
+        if self.path.is_from_path_attr() {
+            raw_cookie = raw_cookie.path(String::from(&self.path));
+        }
+        if let CookieExpiration::AtUtc(utc_tm) = self.expires {
+            raw_cookie = raw_cookie.expires(utc_tm);
+        }
+        if let Some(secure) = self.secure {
+            raw_cookie = raw_cookie.secure(secure);
+        }
+        if let Some(http_only) = self.http_only {
+            raw_cookie = raw_cookie.http_only(http_only);
+        }
+        if let Some(same_site) = self.same_site {
+            raw_cookie = raw_cookie.same_site(same_site);
+        }
+        let mut raw_cookie = raw_cookie.build();
+        let error = |e: CookieError| e.with_raw_cookie(&raw_cookie);
+        let invalid = |reason: &str| {
+            error(CookieError::from(CookieErrorKind::Parse).with_source(reason.into()))
+        };
+
+        let name = raw_cookie.name();
+        if name.is_empty()
+            || name
+                .chars()
+                .any(|c| c.is_control() || c.is_whitespace() || c == ';' || c == '=')
+        {
+            return Err(invalid("invalid cookie name"));
+        }
+        if raw_cookie
+            .value()
+            .chars()
+            .any(|c| c.is_control() || c == ';')
+        {
+            return Err(invalid("invalid cookie value"));
+        }
+        let has_prefix = |prefix: &str| {
+            name.get(..prefix.len())
+                .map_or(false, |p| p.eq_ignore_ascii_case(prefix))
+        };
+        let secure = self.secure == Some(true);
+        if has_prefix("__Secure-") && !secure {
+            return Err(invalid("__Secure- prefixed cookie is not Secure"));
+        }
+        if has_prefix("__Host-")
+            && !(secure && &*self.path == "/" && matches!(self.domain, CookieDomain::HostOnly(_)))
+        {
+            return Err(invalid(
+                "__Host- prefixed cookie is not Secure, host-only, with path /",
+            ));
+        }
+
+        let domain = match self.domain {
+            CookieDomain::HostOnly(host) => {
+                CookieDomain::HostOnly(canonical_host(&host).map_err(error)?)
+            }
+            CookieDomain::Suffix(suffix) => {
+                let suffix = suffix.strip_prefix('.').unwrap_or(&suffix);
+                let suffix = canonical_host(suffix).map_err(error)?;
+                if is_ip_address(&suffix) {
+                    return Err(invalid("domain-attribute is an IP address"));
+                }
+                CookieDomain::Suffix(suffix)
+            }
+            CookieDomain::NotPresent | CookieDomain::Empty => {
+                return Err(error(CookieErrorKind::UnspecifiedDomain.into()));
+            }
+        };
+        if let CookieDomain::Suffix(ref suffix) = domain {
+            raw_cookie.set_domain(suffix.clone());
+        }
+        Ok(Cookie::from_parts(
+            raw_cookie,
+            self.path,
+            domain,
+            self.expires,
+            self.priority,
+        ))
+    }
+}
+
+/// Returns true if `host` is an IPv4 address, or a bracketed IPv6 address as held by
+/// `CookieDomain::HostOnly`
+fn is_ip_address(host: &str) -> bool {
+    let host = host
+        .strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host);
+    !is_host_name(host)
+}
+
+/// The canonical form of `host`, a domain name or IP address
+fn canonical_host(host: &str) -> Result<String, CookieError> {
+    if is_ip_address(host) {
+        return Ok(host.to_owned());
+    }
+    let host = idna::domain_to_ascii(host).map_err(|e| {
+        CookieError::from(CookieErrorKind::Parse).with_source(IdnaErrors::from(e).into())
+    })?;
+    if host.is_empty() {
+        return Err(CookieErrorKind::UnspecifiedDomain.into());
+    }
+    Ok(host)
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{Duration, OffsetDateTime};
+
+    use crate::utils::test as test_utils;
+    use crate::{
+        Cookie, CookieDomain, CookieErrorKind, CookieExpiration, CookiePath, CookiePriority,
+        CookieStore, RawCookie, SameSite, StoreAction,
+    };
+
+    #[test]
+    fn build() {
+        let expires = OffsetDateTime::now_utc() + Duration::hours(1);
+        let cookie = Cookie::builder("session", "abc")
+            .with_domain(CookieDomain::Suffix(".Example.COM".to_owned()))
+            .with_path(CookiePath::parse("/app").unwrap())
+            .with_expires(CookieExpiration::from(expires))
+            .with_secure(true)
+            .with_http_only(true)
+            .with_same_site(SameSite::Strict)
+            .with_priority(CookiePriority::High)
+            .build()
+            .unwrap();
+        assert_eq!(("session", "abc"), cookie.name_value());
+        assert_eq!(
+            CookieDomain::Suffix("example.com".to_owned()),
+            cookie.domain
+        );
+        assert_eq!("/app", &*cookie.path);
+        assert_eq!(CookieExpiration::from(expires), cookie.expires);
+        assert_eq!(Some(true), cookie.secure());
+        assert_eq!(Some(true), cookie.http_only());
+        assert_eq!(Some(SameSite::Strict), cookie.same_site());
+        assert_eq!(CookiePriority::High, cookie.priority());
+        assert_eq!(Some("example.com"), cookie.domain());
+        assert_eq!(None, cookie.set_cookie_header());
+        assert!(cookie.matches(&test_utils::url("https://www.example.com/app/page")));
+        assert!(!cookie.matches(&test_utils::url("http://www.example.com/app/page")));
+
+        let url = test_utils::url("https://www.example.com/");
+        let mut store = CookieStore::default();
+        assert_eq!(StoreAction::Inserted, store.insert(cookie, &url).unwrap());
+        assert!(store.contains("example.com", "/app", "session"));
+    }
+
+    #[test]
+    fn defaults() {
+        let cookie = Cookie::builder("a", "1")
+            .with_domain(CookieDomain::HostOnly("127.0.0.1".to_owned()))
+            .build()
+            .unwrap();
+        assert_eq!(
+            CookieDomain::HostOnly("127.0.0.1".to_owned()),
+            cookie.domain
+        );
+        assert_eq!("/", &*cookie.path);
+        assert!(!cookie.is_persistent());
+        assert_eq!(None, cookie.secure());
+        assert_eq!(None, cookie.same_site());
+        assert_eq!(CookiePriority::Medium, cookie.priority());
+        assert_eq!(
+            "a=1; Path=/",
+            RawCookie::from(cookie.into_owned()).to_string()
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let host_only = || CookieDomain::HostOnly("example.com".to_owned());
+        for (builder, kind) in [
+            (
+                Cookie::builder("a", "1"),
+                CookieErrorKind::UnspecifiedDomain,
+            ),
+            (
+                Cookie::builder("a", "1").with_domain(CookieDomain::Empty),
+                CookieErrorKind::UnspecifiedDomain,
+            ),
+            (
+                Cookie::builder("a", "1").with_domain(CookieDomain::HostOnly(String::new())),
+                CookieErrorKind::UnspecifiedDomain,
+            ),
+            (
+                Cookie::builder("", "1").with_domain(host_only()),
+                CookieErrorKind::Parse,
+            ),
+            (
+                Cookie::builder("a b", "1").with_domain(host_only()),
+                CookieErrorKind::Parse,
+            ),
+            (
+                Cookie::builder("a", "1; Secure").with_domain(host_only()),
+                CookieErrorKind::Parse,
+            ),
+            (
+                Cookie::builder("a", "1").with_domain(CookieDomain::Suffix("xn--a.com".to_owned())),
+                CookieErrorKind::Parse,
+            ),
+            (
+                Cookie::builder("a", "1").with_domain(CookieDomain::Suffix("10.0.0.1".to_owned())),
+                CookieErrorKind::Parse,
+            ),
+            (
+                Cookie::builder("__Secure-a", "1").with_domain(host_only()),
+                CookieErrorKind::Parse,
+            ),
+            (
+                Cookie::builder("__host-a", "1")
+                    .with_domain(host_only())
+                    .with_secure(true)
+                    .with_path(CookiePath::parse("/app").unwrap()),
+                CookieErrorKind::Parse,
+            ),
+            (
+                Cookie::builder("__Host-a", "1")
+                    .with_domain(CookieDomain::Suffix("example.com".to_owned()))
+                    .with_secure(true),
+                CookieErrorKind::Parse,
+            ),
+            (
+                Cookie::builder("__Host-a", "1").with_domain(host_only()),
+                CookieErrorKind::Parse,
+            ),
+        ] {
+            let e = builder.clone().build().unwrap_err();
+            assert_eq!(kind, e.kind(), "{builder:?}");
+        }
+        let e = Cookie::builder("a", "1; Secure")
+            .with_domain(host_only())
+            .build()
+            .unwrap_err();
+        assert_eq!(Some("a"), e.cookie_name());
+    }
+
+    #[test]
+    fn prefixes() {
+        let host_only = || CookieDomain::HostOnly("example.com".to_owned());
+        let cookie = Cookie::builder("__Secure-a", "1")
+            .with_domain(CookieDomain::Suffix("example.com".to_owned()))
+            .with_path(CookiePath::parse("/app").unwrap())
+            .with_secure(true)
+            .build()
+            .unwrap();
+        assert_eq!("__Secure-a", cookie.name());
+        let cookie = Cookie::builder("__Host-a", "1")
+            .with_domain(host_only())
+            .with_secure(true)
+            .build()
+            .unwrap();
+        assert_eq!("__Host-a", cookie.name());
+        // the prefix is only checked at the start of the name
+        assert!(Cookie::builder("a__Host-", "1")
+            .with_domain(host_only())
+            .build()
+            .is_ok());
+    }
+}
@@ -0,0 +1,236 @@
+//! The lenient cookie-date parsing algorithm of [IETF RFC6265 Section
+//! 5.1.1](https://datatracker.ietf.org/doc/html/rfc6265#section-5.1.1), used when the `cookie`
+//! crate does not understand the Expires attribute of a Set-Cookie header
+
+use time::{Date, Month, OffsetDateTime, Time};
+
+const MONTHS: [&[u8; 3]; 12] = [
+    b"jan", b"feb", b"mar", b"apr", b"may", b"jun", b"jul", b"aug", b"sep", b"oct", b"nov", b"dec",
+];
+
+// delimiter = %x09 / %x20-2F / %x3B-40 / %x5B-60 / %x7B-7E
+fn is_delimiter(b: u8) -> bool {
+    matches!(b, 0x09 | 0x20..=0x2F | 0x3B..=0x40 | 0x5B..=0x60 | 0x7B..=0x7E)
+}
+
+/// The value of the leading run of digits of `token`, if there are between `min` and `max` of
+/// them, along with the remainder of `token`
+fn leading_digits(token: &[u8], min: usize, max: usize) -> Option<(u32, &[u8])> {
+    let len = token.iter().take_while(|b| b.is_ascii_digit()).count();
+    if len < min || len > max {
+        return None;
+    }
+    let value = token[..len]
+        .iter()
+        .fold(0, |value, b| value * 10 + u32::from(b - b'0'));
+    Some((value, &token[len..]))
+}
+
+// time = hms-time ( non-digit *OCTET )
+// hms-time = time-field ":" time-field ":" time-field
+// time-field = 1*2DIGIT
+fn time_token(token: &[u8]) -> Option<(u32, u32, u32)> {
+    let (hour, rest) = leading_digits(token, 1, 2)?;
+    let rest = rest.strip_prefix(b":")?;
+    let (minute, rest) = leading_digits(rest, 1, 2)?;
+    let rest = rest.strip_prefix(b":")?;
+    let (second, _) = leading_digits(rest, 1, 2)?;
+    Some((hour, minute, second))
+}
+
+// month = ( "jan" / "feb" / "mar" / "apr" / "may" / "jun" / "jul" / "aug" / "sep" / "oct" /
+//           "nov" / "dec" ) *OCTET
+fn month_token(token: &[u8]) -> Option<Month> {
+    let prefix = token.get(..3)?.to_ascii_lowercase();
+    let index = MONTHS.iter().position(|m| m[..] == prefix[..])?;
+    Month::try_from(index as u8 + 1).ok()
+}
+
+/// Parse `cookie_date`, e.g. the value of an Expires attribute, per the algorithm of RFC6265
+/// Section 5.1.1. Returns `None` if the algorithm fails to find a valid date and time.
+pub(crate) fn parse(cookie_date: &str) -> Option<OffsetDateTime> {
+    let mut time = None;
+    let mut day_of_month = None;
+    let mut month = None;
+    let mut year = None;
+    for token in cookie_date
+        .as_bytes()
+        .split(|b| is_delimiter(*b))
+        .filter(|t| !t.is_empty())
+    {
+        if time.is_none() {
+            if let Some(t) = time_token(token) {
+                time = Some(t);
+                continue;
+            }
+        }
+        // day-of-month = 1*2DIGIT ( non-digit *OCTET )
+        if day_of_month.is_none() {
+            if let Some((d, _)) = leading_digits(token, 1, 2) {
+                day_of_month = Some(d);
+                continue;
+            }
+        }
+        if month.is_none() {
+            if let Some(m) = month_token(token) {
+                month = Some(m);
+                continue;
+            }
+        }
+        // year = 2*4DIGIT ( non-digit *OCTET )
+        if year.is_none() {
+            if let Some((y, _)) = leading_digits(token, 2, 4) {
+                year = Some(y);
+            }
+        }
+    }
+    let (hour, minute, second) = time?;
+    let year = match year? {
+        y @ 70..=99 => y + 1900,
+        y @ 0..=69 => y + 2000,
+        y => y,
+    };
+    let day_of_month = day_of_month?;
+    if !(1..=31).contains(&day_of_month) || year < 1601 || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    let date = Date::from_calendar_date(year as i32, month?, day_of_month as u8).ok()?;
+    let time = Time::from_hms(hour as u8, minute as u8, second as u8).ok()?;
+    Some(date.with_time(time).assume_utc())
+}
+
+/// The value of the last Expires attribute of the Set-Cookie header value `set_cookie`, if any
+pub(crate) fn expires_attribute(set_cookie: &str) -> Option<&str> {
+    crate::utils::attributes(set_cookie)
+        .filter(|(name, _)| name.eq_ignore_ascii_case("expires"))
+        .filter_map(|(_, value)| value)
+        .last()
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::{expires_attribute, parse};
+
+    // Cases from the http-state test corpus, dates/examples.json and dates/bsd-examples.json
+    #[test]
+    fn corpus() {
+        for (cookie_date, expected) in [
+            (
+                "Mon, 10-Dec-2007 17:02:24 GMT",
+                datetime!(2007-12-10 17:02:24 UTC),
+            ),
+            (
+                "Wed, 09 Dec 2009 16:27:23 GMT",
+                datetime!(2009-12-09 16:27:23 UTC),
+            ),
+            (
+                "Thursday, 01-Jan-1970 00:00:00 GMT",
+                datetime!(1970-01-01 00:00:00 UTC),
+            ),
+            (
+                "Mon Dec 10 16:32:30 2007 GMT",
+                datetime!(2007-12-10 16:32:30 UTC),
+            ),
+            (
+                "Wednesday, 01-Jan-10 00:00:00 GMT",
+                datetime!(2010-01-01 00:00:00 UTC),
+            ),
+            (
+                "Mon, 10-Dec-07 20:35:03 GMT",
+                datetime!(2007-12-10 20:35:03 UTC),
+            ),
+            (
+                "Wed, 1 Jan 2020 00:00:00 GMT",
+                datetime!(2020-01-01 00:00:00 UTC),
+            ),
+            (
+                "Saturday, 8-Dec-2012 21:24:09 GMT",
+                datetime!(2012-12-08 21:24:09 UTC),
+            ),
+            (
+                "Thu, 31 Dec 23:55:55 2107 GMT",
+                datetime!(2107-12-31 23:55:55 UTC),
+            ),
+            (
+                "Sat, 15-Apr-17 21:01:22 GMT",
+                datetime!(2017-04-15 21:01:22 UTC),
+            ),
+            (
+                "Thu, 19/Apr\\2007 16:00:00 GMT",
+                datetime!(2007-04-19 16:00:00 UTC),
+            ),
+            (
+                ", 1-Jan-2003 00:00:00 GMT",
+                datetime!(2003-01-01 00:00:00 UTC),
+            ),
+            (
+                "Wed,18-Apr-07 22:50:12 GMT",
+                datetime!(2007-04-18 22:50:12 UTC),
+            ),
+            (
+                "WillyWonka  ,  18-apr-07   22:50:12",
+                datetime!(2007-04-18 22:50:12 UTC),
+            ),
+            (
+                "Mon, 18-Apr-77 22:50:13 GMT",
+                datetime!(1977-04-18 22:50:13 UTC),
+            ),
+            (
+                "\"Sat, 15-Apr-17\\\"21:01:22\\\"GMT\"",
+                datetime!(2017-04-15 21:01:22 UTC),
+            ),
+            (
+                "Partyday, 18- April-07 22:50:12",
+                datetime!(2007-04-18 22:50:12 UTC),
+            ),
+            (
+                "Wednes, 1-Januar-2003 00:00:00 GMT",
+                datetime!(2003-01-01 00:00:00 UTC),
+            ),
+            (
+                "Thu, 10 Apr 2008 13:00:00 GMT+0100",
+                datetime!(2008-04-10 13:00:00 UTC),
+            ),
+            (
+                "Thu, 10 Apr 2008 13:00:00 GMT-01:00",
+                datetime!(2008-04-10 13:00:00 UTC),
+            ),
+        ] {
+            assert_eq!(Some(expected), parse(cookie_date), "{cookie_date}");
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        for cookie_date in [
+            "",
+            "Thu, 01-Jan-1600 00:00:00 GMT",
+            "Wed, 00-Jan-2020 00:00:00 GMT",
+            "Wed, 32-Jan-2020 00:00:00 GMT",
+            "Wed, 31-Feb-2020 00:00:00 GMT",
+            "Wed, 01-Jan-2020 24:00:00 GMT",
+            "Wed, 01-Jan-2020 00:60:00 GMT",
+            "Wed, 01-Jan-2020 00:00:60 GMT",
+            "Wed, 01-Jan-2020 GMT",
+            "Wed, 01-Foo-2020 00:00:00 GMT",
+            "Wed, 01-Jan-7 00:00:00 GMT",
+            "Wed, 01-Jan-12345 00:00:00 GMT",
+            "Wed, 01-Jan-2020 123:00:00 GMT",
+        ] {
+            assert_eq!(None, parse(cookie_date), "{cookie_date}");
+        }
+    }
+
+    #[test]
+    fn expires() {
+        assert_eq!(
+            Some("Wed, 09-Jun-2021 10:18:14 GMT"),
+            expires_attribute("a=b; Path=/; expires = Wed, 09-Jun-2021 10:18:14 GMT ; Secure")
+        );
+        assert_eq!(Some("2"), expires_attribute("a=b; Expires=1; EXPIRES=2"));
+        assert_eq!(None, expires_attribute("expires=1"));
+        assert_eq!(None, expires_attribute("a=b; Max-Age=1"));
+    }
+}
@@ -7,12 +7,60 @@ use std::convert::TryFrom;
 use url::{Host, Url};
 
 use crate::utils::is_host_name;
-use crate::CookieError;
+use crate::{CookieError, CookieErrorKind};
 
-pub fn is_match(domain: &str, request_url: &Url) -> bool {
-    CookieDomain::try_from(domain)
-        .map(|domain| domain.matches(request_url))
-        .unwrap_or(false)
+/// The domains which `host` may domain-match: `host` itself followed, if it is a host name, by
+/// each of its parent domains (e.g. `www.example.com`, `example.com`, `com`)
+pub(crate) fn match_candidates(host: &str) -> impl Iterator<Item = &str> {
+    let is_host_name = is_host_name(host);
+    std::iter::successors(Some(host), move |domain| {
+        if is_host_name {
+            domain.find('.').map(|i| &domain[i + 1..])
+        } else {
+            None
+        }
+    })
+    .filter(|domain| !domain.is_empty())
+}
+
+/// Returns true if `host` and `domain` are identical, or `host` is a subdomain of `domain`
+pub(crate) fn is_same_or_subdomain(host: &str, domain: &str) -> bool {
+    host == domain
+        || (is_host_name(host)
+            && host.ends_with(domain)
+            && host[..host.len() - domain.len()].ends_with('.'))
+}
+
+/// Canonicalize a user-supplied domain for comparison with stored cookie domains: surrounding
+/// whitespace and any leading '.' are removed, and the remainder converted per IDNA
+pub(crate) fn canonical_domain(domain: &str) -> String {
+    let domain = domain.trim();
+    let domain = domain.strip_prefix('.').unwrap_or(domain);
+    idna::domain_to_ascii(domain).unwrap_or_else(|_| domain.to_lowercase())
+}
+
+/// The registrable domain of `host` per `psl`, or `host` itself if it has none (e.g. an IP
+/// address, or a host which is itself a public suffix)
+#[cfg(feature = "public_suffix")]
+pub(crate) fn registrable_domain<'a>(host: &'a str, psl: &List) -> &'a str {
+    if !is_host_name(host) {
+        return host;
+    }
+    psl.domain(host.as_bytes())
+        .and_then(|domain| std::str::from_utf8(domain.as_bytes()).ok())
+        .unwrap_or(host)
+}
+
+/// An approximation of the registrable domain of `host` in the absence of a public suffix list:
+/// its last two labels, or `host` itself if it has fewer labels or is an IP address
+pub(crate) fn approximate_registrable_domain(host: &str) -> &str {
+    if !is_host_name(host) {
+        return host;
+    }
+    match host.rmatch_indices('.').nth(1) {
+        Some((i, _)) => &host[i + 1..],
+        None => host,
+    }
 }
 
 /// The domain of a `Cookie`
@@ -54,7 +102,7 @@ impl CookieDomain {
     pub fn host_only(request_url: &Url) -> Result<CookieDomain, CookieError> {
         request_url
             .host()
-            .ok_or(CookieError::NonRelativeScheme)
+            .ok_or_else(|| CookieErrorKind::NonRelativeScheme.into())
             .map(|h| match h {
                 Host::Domain(d) => CookieDomain::HostOnly(d.into()),
                 Host::Ipv4(addr) => CookieDomain::HostOnly(format!("{addr}")),
@@ -183,9 +231,23 @@ mod tests {
     use std::convert::TryFrom;
     use url::Url;
 
-    use super::CookieDomain;
+    use super::{match_candidates, CookieDomain};
     use crate::utils::test::*;
 
+    #[test]
+    fn candidates() {
+        fn candidates(host: &str) -> Vec<&str> {
+            match_candidates(host).collect()
+        }
+        assert_eq!(
+            vec!["www.example.com", "example.com", "com"],
+            candidates("www.example.com")
+        );
+        assert_eq!(vec!["localhost"], candidates("localhost"));
+        assert_eq!(vec!["127.0.0.1"], candidates("127.0.0.1"));
+        assert_eq!(vec!["[::1]"], candidates("[::1]"));
+    }
+
     #[inline]
     fn matches(expected: bool, cookie_domain: &CookieDomain, url: &str) {
         let url = Url::parse(url).unwrap();
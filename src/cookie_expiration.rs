@@ -33,11 +33,35 @@ impl std::cmp::PartialEq for CookieExpiration {
 }
 
 impl CookieExpiration {
-    /// Indicates if the `Cookie` is expired as of *now*.
+    /// Indicates if the `Cookie` is expired as of *now*, per the system clock; to consult the
+    /// [`Clock`](crate::Clock) of a store, use
+    /// [`expires_by`](CookieExpiration::expires_by)`(&store.now())`.
     pub fn is_expired(&self) -> bool {
         self.expires_by(&time::OffsetDateTime::now_utc())
     }
 
+    /// The expiration of a `Cookie` with a Max-Age of `duration`, received at `now_utc`
+    pub fn from_max_age_at(duration: time::Duration, now_utc: OffsetDateTime) -> CookieExpiration {
+        // If delta-seconds is less than or equal to zero (0), let expiry-time
+        //    be the earliest representable date and time.  Otherwise, let the
+        //    expiry-time be the current date and time plus delta-seconds seconds.
+        let utc_tm = if duration.is_zero() {
+            time::OffsetDateTime::UNIX_EPOCH
+        } else {
+            let d = (MAX_RFC3339 - now_utc).min(duration);
+            now_utc + d
+        };
+        CookieExpiration::from(utc_tm)
+    }
+
+    /// The expiration of a `Cookie` with an Expires attribute of `cookie_date`, parsed per the
+    /// lenient algorithm of [RFC6265 Section
+    /// 5.1.1](https://datatracker.ietf.org/doc/html/rfc6265#section-5.1.1). Returns `None` if
+    /// `cookie_date` is not a valid cookie-date, in which case the attribute is ignored.
+    pub fn from_cookie_date(cookie_date: &str) -> Option<CookieExpiration> {
+        crate::cookie_date::parse(cookie_date).map(CookieExpiration::from)
+    }
+
     /// Indicates if the `Cookie` expires as of `utc_tm`.
     pub fn expires_by(&self, utc_tm: &time::OffsetDateTime) -> bool {
         match *self {
@@ -78,17 +102,7 @@ impl From<cookie::Expiration> for CookieExpiration {
 
 impl From<time::Duration> for CookieExpiration {
     fn from(duration: time::Duration) -> Self {
-        // If delta-seconds is less than or equal to zero (0), let expiry-time
-        //    be the earliest representable date and time.  Otherwise, let the
-        //    expiry-time be the current date and time plus delta-seconds seconds.
-        let utc_tm = if duration.is_zero() {
-            time::OffsetDateTime::UNIX_EPOCH
-        } else {
-            let now_utc = time::OffsetDateTime::now_utc();
-            let d = (MAX_RFC3339 - now_utc).min(duration);
-            now_utc + d
-        };
-        CookieExpiration::from(utc_tm)
+        CookieExpiration::from_max_age_at(duration, time::OffsetDateTime::now_utc())
     }
 }
 
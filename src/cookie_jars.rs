@@ -0,0 +1,358 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::cookie::Cookie;
+use crate::cookie_query::CookieQuery;
+use crate::cookie_store::StoreResult;
+use crate::CookieStore;
+
+/// A set of [`CookieStore`]s ("profiles"), e.g. one per logged-in account, keyed by `K` and
+/// sharing the configuration of a template store: the public suffix list, [`CookiePolicy`],
+/// third-party blocking, and [`Clock`]. The public suffix list is shared rather than copied
+/// among profiles.
+///
+/// Observers are not shared; add them to individual profiles as required.
+///
+/// ```
+/// # use cookie_store::{CookieJars, CookieStore};
+/// let mut jars = CookieJars::new(CookieStore::default().with_third_party_blocking(["example.com"]));
+/// let url = url::Url::parse("https://example.com/").unwrap();
+/// jars.profile("alice").parse("session=a", &url).unwrap();
+/// jars.clone_profile("alice", "bob");
+/// jars.profile("bob").parse("session=b", &url).unwrap();
+/// assert_eq!("a", jars.get("alice").unwrap().get("example.com", "/", "session").unwrap().value());
+/// assert_eq!("b", jars.get("bob").unwrap().get("example.com", "/", "session").unwrap().value());
+/// ```
+///
+/// [`CookiePolicy`]: crate::CookiePolicy
+/// [`Clock`]: crate::Clock
+#[derive(Debug, Clone)]
+pub struct CookieJars<K> {
+    /// An empty store holding the shared configuration
+    template: CookieStore,
+    profiles: HashMap<K, CookieStore>,
+}
+
+impl<K: Eq + Hash> Default for CookieJars<K> {
+    fn default() -> Self {
+        CookieJars::new(CookieStore::default())
+    }
+}
+
+impl<K: Eq + Hash> CookieJars<K> {
+    /// A set of profiles sharing the configuration of `template`; any cookies in `template` are
+    /// ignored
+    pub fn new(template: CookieStore) -> CookieJars<K> {
+        CookieJars {
+            template: template.empty(),
+            profiles: HashMap::new(),
+        }
+    }
+
+    /// The profile for `key`, created empty if it does not exist
+    ///
+    /// Replacing a profile through the returned reference (e.g. `*jars.profile(key) = store`)
+    /// discards the shared configuration; use [`CookieJars::insert`] instead, which applies it to
+    /// `store`.
+    pub fn profile(&mut self, key: K) -> &mut CookieStore {
+        let template = &self.template;
+        self.profiles.entry(key).or_insert_with(|| template.empty())
+    }
+
+    /// The profile for `key`, if it exists
+    pub fn get<Q>(&self, key: &Q) -> Option<&CookieStore>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.profiles.get(key)
+    }
+
+    /// The profile for `key`, if it exists
+    ///
+    /// Replacing a profile through the returned reference (e.g.
+    /// `*jars.get_mut(key).unwrap() = store`) discards the shared configuration; use
+    /// [`CookieJars::insert`] instead, which applies it to `store`.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut CookieStore>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.profiles.get_mut(key)
+    }
+
+    /// Returns true if a profile exists for `key`
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.profiles.contains_key(key)
+    }
+
+    /// Add the cookies of `store` as the profile for `key`, with the shared configuration
+    /// replacing that of `store`. Returns the profile previously stored for `key`, if any.
+    pub fn insert(&mut self, key: K, store: CookieStore) -> Option<CookieStore> {
+        self.profiles
+            .insert(key, store.with_config_of(&self.template))
+    }
+
+    /// Removes the profile for `key`, returning it if it existed
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<CookieStore>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.profiles.remove(key)
+    }
+
+    /// Copy the cookies of the profile for `from` into a new profile for `to`, replacing any
+    /// existing profile for `to`. Returns false, leaving the profiles unchanged, if there is no
+    /// profile for `from`.
+    pub fn clone_profile<Q>(&mut self, from: &Q, to: K) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        match self.profiles.get(from) {
+            Some(store) => {
+                let cloned = store.clone().with_config_of(&self.template);
+                self.profiles.insert(to, cloned);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The number of profiles
+    pub fn len(&self) -> usize {
+        self.profiles.len()
+    }
+
+    /// Returns true if there are no profiles
+    pub fn is_empty(&self) -> bool {
+        self.profiles.is_empty()
+    }
+
+    /// An iterator visiting the keys of all profiles, in arbitrary order
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.profiles.keys()
+    }
+
+    /// An iterator visiting all profiles, in arbitrary order
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &CookieStore)> {
+        self.profiles.iter()
+    }
+
+    /// An iterator visiting all profiles mutably, in arbitrary order
+    ///
+    /// Replacing a profile through one of the yielded references discards the shared
+    /// configuration; use [`CookieJars::insert`] instead.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&K, &mut CookieStore)> {
+        self.profiles.iter_mut()
+    }
+
+    /// Remove all __expired__ cookies from every profile, returning the number removed
+    pub fn purge_expired(&mut self) -> usize {
+        self.profiles
+            .values_mut()
+            .map(|store| store.purge_expired().len())
+            .sum()
+    }
+
+    /// Remove all __non-persistent__ cookies from every profile, returning the number removed;
+    /// see [`CookieStore::end_session`]
+    pub fn end_session(&mut self) -> usize {
+        self.profiles
+            .values_mut()
+            .map(|store| store.end_session().len())
+            .sum()
+    }
+
+    /// Expire in place the cookies selected by `query` in every profile, returning the number
+    /// expired; see [`CookieStore::expire_selected`]
+    pub fn expire_selected(&mut self, query: &CookieQuery) -> usize {
+        self.profiles
+            .values_mut()
+            .map(|store| store.expire_selected(query))
+            .sum()
+    }
+
+    /// Remove the cookies selected by `query` from every profile, returning them by profile
+    /// key; profiles from which no cookies were removed are omitted
+    pub fn remove_selected(&mut self, query: &CookieQuery) -> Vec<(&K, Vec<Cookie<'static>>)> {
+        self.profiles
+            .iter_mut()
+            .map(|(key, store)| (key, store.remove_selected(query)))
+            .filter(|(_, removed)| !removed.is_empty())
+            .collect()
+    }
+
+    /// Load the profile for `key` using `load`, e.g. one of the `load` functions of the
+    /// [`serde`](crate::serde) modules, replacing any existing profile for `key`. The loaded
+    /// store takes the shared configuration. Returns the profile previously stored for `key`,
+    /// if any.
+    ///
+    /// ```
+    /// # use cookie_store::{CookieJars, CookieStore};
+    /// # #[cfg(feature = "serde_json")]
+    /// # {
+    /// let mut jars = CookieJars::default();
+    /// let saved = r#"[{"raw_cookie":"a=1; Max-Age=3600","path":["/",false],"domain":{"HostOnly":"example.com"},"expires":{"AtUtc":"3000-01-01T00:00:00Z"}}]"#;
+    /// jars.load_profile(1, || cookie_store::serde::json::load(saved.as_bytes())).unwrap();
+    /// assert!(jars.get(&1).unwrap().contains("example.com", "/", "a"));
+    /// # }
+    /// ```
+    pub fn load_profile<F>(&mut self, key: K, load: F) -> StoreResult<Option<CookieStore>>
+    where
+        F: FnOnce() -> StoreResult<CookieStore>,
+    {
+        let store = load()?;
+        Ok(self.insert(key, store))
+    }
+
+    /// Save the profile for `key` using `save`, e.g. a closure calling one of the `save`
+    /// functions of the [`serde`](crate::serde) modules. Returns `Ok(false)`, without calling
+    /// `save`, if there is no profile for `key`.
+    pub fn save_profile<Q, F>(&self, key: &Q, save: F) -> StoreResult<bool>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+        F: FnOnce(&CookieStore) -> StoreResult<()>,
+    {
+        match self.profiles.get(key) {
+            Some(store) => save(store).map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Save every profile using `save`, stopping at the first error
+    pub fn save_all<F>(&self, mut save: F) -> StoreResult<()>
+    where
+        F: FnMut(&K, &CookieStore) -> StoreResult<()>,
+    {
+        self.profiles
+            .iter()
+            .try_for_each(|(key, store)| save(key, store))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::Duration;
+
+    use super::CookieJars;
+    use crate::utils::test as test_utils;
+    use crate::{CookieQuery, CookieStore, ManualClock};
+
+    #[test]
+    fn profiles() {
+        let clock = ManualClock::default();
+        let mut jars = CookieJars::new(
+            CookieStore::default()
+                .with_clock(clock.clone())
+                .with_third_party_blocking(Vec::<&str>::new()),
+        );
+        let url = test_utils::url("https://www.example.com/");
+        let other = test_utils::url("https://other.test/");
+        jars.profile(1).parse("session=1", &url).unwrap();
+        jars.profile(1).parse("short=1; Max-Age=60", &url).unwrap();
+        jars.profile(2).parse("short=2; Max-Age=60", &url).unwrap();
+        assert_eq!(2, jars.len());
+
+        // configuration is shared, including with inserted and cloned profiles
+        assert!(jars
+            .profile(1)
+            .insert_for_site(
+                test_utils::make_cookie("tp=1", "https://www.example.com/", None, None),
+                &url,
+                &other
+            )
+            .is_err());
+        let mut unconfigured = CookieStore::default();
+        unconfigured.parse("loaded=3", &url).unwrap();
+        assert!(jars.insert(3, unconfigured).is_none());
+        assert!(jars.clone_profile(&1, 4));
+        assert!(!jars.clone_profile(&5, 6));
+        for key in [3, 4] {
+            assert!(jars
+                .profile(key)
+                .insert_for_site(
+                    test_utils::make_cookie("tp=1", "https://www.example.com/", None, None),
+                    &url,
+                    &other
+                )
+                .is_err());
+        }
+        assert!(jars
+            .get(&4)
+            .unwrap()
+            .contains("www.example.com", "/", "session"));
+        jars.profile(4).remove("www.example.com", "/", "session");
+        assert!(jars
+            .get(&1)
+            .unwrap()
+            .contains("www.example.com", "/", "session"));
+
+        // the shared clock drives expiry across profiles
+        clock.advance(Duration::minutes(2));
+        assert_eq!(3, jars.purge_expired());
+        assert_eq!(2, jars.end_session());
+        assert!(jars.iter().all(|(_, store)| store.iter_any().count() == 0));
+
+        jars.profile(1).parse("a=1", &url).unwrap();
+        jars.profile(2).parse("a=2", &url).unwrap();
+        assert_eq!(2, jars.expire_selected(&CookieQuery::new().name("a")));
+        assert!(jars
+            .remove_selected(&CookieQuery::new().name("a"))
+            .is_empty());
+        assert_eq!(
+            2,
+            jars.remove_selected(&CookieQuery::new().name("a").include_expired(true))
+                .len()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde_json")]
+    fn persistence() {
+        let url = test_utils::url("https://example.com/");
+        let mut jars = CookieJars::new(CookieStore::default().with_third_party_blocking(["x"]));
+        jars.profile("a")
+            .parse("persistent=a; Max-Age=3600", &url)
+            .unwrap();
+        let mut saved = Vec::new();
+        assert!(jars
+            .save_profile("a", |store| crate::serde::json::save(store, &mut saved))
+            .unwrap());
+        assert!(!jars.save_profile("b", |_| unreachable!()).unwrap());
+
+        jars.load_profile("b", || crate::serde::json::load(&saved[..]))
+            .unwrap();
+        assert!(jars
+            .get("b")
+            .unwrap()
+            .contains("example.com", "/", "persistent"));
+        assert!(jars
+            .profile("b")
+            .insert_for_site(
+                test_utils::make_cookie("tp=1", "https://example.com/", None, None),
+                &url,
+                &test_utils::url("https://other.test/")
+            )
+            .is_err());
+
+        let mut count = 0;
+        jars.save_all(|_, _| {
+            count += 1;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(2, count);
+        assert!(jars
+            .load_profile("c", || Err(crate::StoreError::Other("bad".into())))
+            .is_err());
+        assert!(!jars.contains("c"));
+    }
+}
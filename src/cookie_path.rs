@@ -4,10 +4,14 @@ use std::cmp::max;
 use std::ops::Deref;
 use url::Url;
 
-/// Returns true if `request_url` path-matches `path` per
+/// Returns true if `request_path` path-matches `cookie_path` per
 /// [IETF RFC6265 Section 5.1.4](https://datatracker.ietf.org/doc/html/rfc6265#section-5.1.4)
-pub fn is_match(path: &str, request_url: &Url) -> bool {
-    CookiePath::parse(path).map_or(false, |cp| cp.matches(request_url))
+pub(crate) fn is_match(cookie_path: &str, request_path: &str) -> bool {
+    // o  The cookie-path and the request-path are identical.
+    cookie_path == request_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/')
+                || &request_path[cookie_path.len()..=cookie_path.len()] == "/"))
 }
 
 /// The path of a `Cookie`
@@ -21,13 +25,7 @@ impl CookiePath {
         if request_url.cannot_be_a_base() {
             false
         } else {
-            let request_path = request_url.path();
-            let cookie_path = &*self.0;
-            // o  The cookie-path and the request-path are identical.
-            cookie_path == request_path
-                || (request_path.starts_with(cookie_path)
-                    && (cookie_path.ends_with('/')
-                        || &request_path[cookie_path.len()..=cookie_path.len()] == "/"))
+            is_match(&self.0, request_url.path())
         }
     }
 
@@ -81,6 +79,11 @@ impl CookiePath {
         }
     }
 
+    /// The path "/", as if set from a Path attribute
+    pub(crate) fn root() -> CookiePath {
+        CookiePath(String::from("/"), true)
+    }
+
     /// Attempt to parse `path` as a `CookiePath`. If `path` does not have a leading "/",
     /// `None` is returned.
     pub fn parse(path: &str) -> Option<CookiePath> {
@@ -0,0 +1,270 @@
+use std::fmt;
+use std::sync::Arc;
+
+use url::Url;
+
+use crate::cookie::Cookie;
+use crate::cookie_domain::{canonical_domain, is_same_or_subdomain};
+
+/// A policy consulted by a [`CookieStore`](crate::CookieStore) when storing and returning
+/// cookies, in addition to the rules of [IETF RFC6265](https://datatracker.ietf.org/doc/html/rfc6265)
+/// (and any public suffix list) which are always applied. Install a policy via
+/// [`CookieStore::with_policy`](crate::CookieStore::with_policy).
+///
+/// Both methods default to accepting the cookie, so implementations need only override the
+/// decisions they care about. Policies may be combined with [`CookiePolicy::and`] and
+/// [`CookiePolicy::or`].
+pub trait CookiePolicy: fmt::Debug + Send + Sync {
+    /// Whether `cookie`, received from `request_url`, may be stored. Called only for cookies
+    /// which would otherwise be accepted; a rejected cookie is ignored entirely, including an
+    /// __expired__ cookie which would have expired an existing entry.
+    fn set_ok(&self, _cookie: &Cookie<'_>, _request_url: &Url) -> bool {
+        true
+    }
+
+    /// Whether the stored `cookie` may be returned for `request_url`. Called only for cookies
+    /// which otherwise match `request_url`.
+    fn return_ok(&self, _cookie: &Cookie<'_>, _request_url: &Url) -> bool {
+        true
+    }
+
+    /// A policy accepting a cookie only if both `self` and `other` accept it
+    fn and<P: CookiePolicy>(self, other: P) -> And<Self, P>
+    where
+        Self: Sized,
+    {
+        And(self, other)
+    }
+
+    /// A policy accepting a cookie if either `self` or `other` accepts it
+    fn or<P: CookiePolicy>(self, other: P) -> Or<Self, P>
+    where
+        Self: Sized,
+    {
+        Or(self, other)
+    }
+}
+
+impl<P: CookiePolicy + ?Sized> CookiePolicy for Arc<P> {
+    fn set_ok(&self, cookie: &Cookie<'_>, request_url: &Url) -> bool {
+        (**self).set_ok(cookie, request_url)
+    }
+
+    fn return_ok(&self, cookie: &Cookie<'_>, request_url: &Url) -> bool {
+        (**self).return_ok(cookie, request_url)
+    }
+}
+
+impl<P: CookiePolicy + ?Sized> CookiePolicy for Box<P> {
+    fn set_ok(&self, cookie: &Cookie<'_>, request_url: &Url) -> bool {
+        (**self).set_ok(cookie, request_url)
+    }
+
+    fn return_ok(&self, cookie: &Cookie<'_>, request_url: &Url) -> bool {
+        (**self).return_ok(cookie, request_url)
+    }
+}
+
+/// The policy of a `CookieStore` with no policy installed: every cookie acceptable per RFC6265
+/// is stored and returned
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DefaultCookiePolicy;
+
+impl CookiePolicy for DefaultCookiePolicy {}
+
+/// Combination of two policies, accepting a cookie only if both accept it. See
+/// [`CookiePolicy::and`].
+#[derive(Debug, Clone)]
+pub struct And<A, B>(pub A, pub B);
+
+impl<A: CookiePolicy, B: CookiePolicy> CookiePolicy for And<A, B> {
+    fn set_ok(&self, cookie: &Cookie<'_>, request_url: &Url) -> bool {
+        self.0.set_ok(cookie, request_url) && self.1.set_ok(cookie, request_url)
+    }
+
+    fn return_ok(&self, cookie: &Cookie<'_>, request_url: &Url) -> bool {
+        self.0.return_ok(cookie, request_url) && self.1.return_ok(cookie, request_url)
+    }
+}
+
+/// Combination of two policies, accepting a cookie if either accepts it. See
+/// [`CookiePolicy::or`].
+#[derive(Debug, Clone)]
+pub struct Or<A, B>(pub A, pub B);
+
+impl<A: CookiePolicy, B: CookiePolicy> CookiePolicy for Or<A, B> {
+    fn set_ok(&self, cookie: &Cookie<'_>, request_url: &Url) -> bool {
+        self.0.set_ok(cookie, request_url) || self.1.set_ok(cookie, request_url)
+    }
+
+    fn return_ok(&self, cookie: &Cookie<'_>, request_url: &Url) -> bool {
+        self.0.return_ok(cookie, request_url) || self.1.return_ok(cookie, request_url)
+    }
+}
+
+/// A list of domains, each of which matches a cookie domain that is identical to it or is one of
+/// its subdomains
+#[derive(Debug, Default, Clone)]
+struct DomainList(Vec<String>);
+
+impl DomainList {
+    fn new<I, S>(domains: I) -> DomainList
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        DomainList(
+            domains
+                .into_iter()
+                .map(|d| canonical_domain(d.as_ref()))
+                .collect(),
+        )
+    }
+
+    fn contains(&self, cookie: &Cookie<'_>) -> bool {
+        cookie.domain.as_cow().map_or(false, |domain| {
+            self.0.iter().any(|d| is_same_or_subdomain(&domain, d))
+        })
+    }
+}
+
+/// A policy rejecting any cookie whose domain is one of the listed domains, or a subdomain of
+/// one, both when storing and returning cookies. Useful for blocking trackers.
+#[derive(Debug, Default, Clone)]
+pub struct BlockedDomains(DomainList);
+
+impl BlockedDomains {
+    pub fn new<I, S>(domains: I) -> BlockedDomains
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        BlockedDomains(DomainList::new(domains))
+    }
+}
+
+impl CookiePolicy for BlockedDomains {
+    fn set_ok(&self, cookie: &Cookie<'_>, _request_url: &Url) -> bool {
+        !self.0.contains(cookie)
+    }
+
+    fn return_ok(&self, cookie: &Cookie<'_>, _request_url: &Url) -> bool {
+        !self.0.contains(cookie)
+    }
+}
+
+/// A policy accepting only cookies whose domain is one of the listed domains, or a subdomain of
+/// one, both when storing and returning cookies. Useful for restricting a client to its target
+/// domains.
+#[derive(Debug, Default, Clone)]
+pub struct AllowedDomains(DomainList);
+
+impl AllowedDomains {
+    pub fn new<I, S>(domains: I) -> AllowedDomains
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        AllowedDomains(DomainList::new(domains))
+    }
+}
+
+impl CookiePolicy for AllowedDomains {
+    fn set_ok(&self, cookie: &Cookie<'_>, _request_url: &Url) -> bool {
+        self.0.contains(cookie)
+    }
+
+    fn return_ok(&self, cookie: &Cookie<'_>, _request_url: &Url) -> bool {
+        self.0.contains(cookie)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use super::{AllowedDomains, BlockedDomains, CookiePolicy};
+    use crate::cookie::Cookie;
+    use crate::utils::test as test_utils;
+    use crate::{CookieErrorKind, CookieStore, StoreAction};
+
+    #[test]
+    fn blocked_domains() {
+        let mut store = CookieStore::default().with_policy(BlockedDomains::new(["Tracker.com"]));
+        for url in ["http://tracker.com/", "http://ads.tracker.com/"] {
+            assert_eq!(
+                Err(CookieErrorKind::PolicyRejected),
+                store
+                    .parse("cookie1=value1", &test_utils::url(url))
+                    .map_err(|e| e.kind())
+            );
+        }
+        let url = test_utils::url("http://nottracker.com/");
+        assert_eq!(
+            Ok(StoreAction::Inserted),
+            store.parse("cookie1=value1", &url)
+        );
+        assert_eq!(1, store.matches(&url).len());
+    }
+
+    #[test]
+    fn allowed_domains() {
+        let mut store =
+            CookieStore::default().with_policy(AllowedDomains::new([".example.com", "api.org"]));
+        for url in [
+            "http://example.com/",
+            "http://www.example.com/",
+            "http://api.org/",
+        ] {
+            assert_eq!(
+                Ok(StoreAction::Inserted),
+                store.parse("cookie1=value1", &test_utils::url(url))
+            );
+        }
+        for url in ["http://example.org/", "http://org/", "http://myapi.org/"] {
+            assert_eq!(
+                Err(CookieErrorKind::PolicyRejected),
+                store
+                    .parse("cookie1=value1", &test_utils::url(url))
+                    .map_err(|e| e.kind())
+            );
+        }
+    }
+
+    #[derive(Debug)]
+    struct NoSecureReturn;
+
+    impl CookiePolicy for NoSecureReturn {
+        fn return_ok(&self, cookie: &Cookie<'_>, _request_url: &Url) -> bool {
+            !cookie.secure().unwrap_or(false)
+        }
+    }
+
+    #[test]
+    fn combinators() {
+        let url = test_utils::url("https://www.example.com/");
+        let mut store = CookieStore::default().with_policy(
+            AllowedDomains::new(["example.com"])
+                .and(NoSecureReturn)
+                .or(AllowedDomains::new(["example.org"])),
+        );
+        assert_eq!(
+            Ok(StoreAction::Inserted),
+            store.parse("cookie1=value1", &url)
+        );
+        assert_eq!(
+            Ok(StoreAction::Inserted),
+            store.parse("cookie2=value2; Secure", &url)
+        );
+        let matched = store.matches(&url);
+        assert_eq!(1, matched.len());
+        assert_eq!("cookie1", matched[0].name());
+
+        let url = test_utils::url("https://example.org/");
+        assert_eq!(
+            Ok(StoreAction::Inserted),
+            store.parse("cookie2=value2; Secure", &url)
+        );
+        assert_eq!(1, store.matches(&url).len());
+    }
+}
@@ -0,0 +1,55 @@
+#[cfg(feature = "serde")]
+use serde_derive::{Deserialize, Serialize};
+
+/// The Priority attribute of a Set-Cookie header, as honored by Chrome: when cookies must be
+/// removed to make room, those of lower priority are removed first. Ordered from `Low` to `High`.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CookiePriority {
+    Low,
+    /// The priority of a `Cookie` without a (recognized) Priority attribute
+    #[default]
+    Medium,
+    High,
+}
+
+impl CookiePriority {
+    /// The priority named by the Priority attribute value `value`, compared case-insensitively;
+    /// `None` if `value` is not one of `Low`, `Medium` or `High`
+    pub fn from_attribute(value: &str) -> Option<CookiePriority> {
+        [
+            ("low", CookiePriority::Low),
+            ("medium", CookiePriority::Medium),
+            ("high", CookiePriority::High),
+        ]
+        .into_iter()
+        .find(|(name, _)| value.eq_ignore_ascii_case(name))
+        .map(|(_, priority)| priority)
+    }
+
+    /// Returns true if this is the default, `Medium`, priority
+    pub fn is_default(&self) -> bool {
+        *self == CookiePriority::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CookiePriority;
+
+    #[test]
+    fn from_attribute() {
+        assert_eq!(
+            Some(CookiePriority::Low),
+            CookiePriority::from_attribute("low")
+        );
+        assert_eq!(
+            Some(CookiePriority::High),
+            CookiePriority::from_attribute("HIGH")
+        );
+        assert_eq!(None, CookiePriority::from_attribute("urgent"));
+        assert!(CookiePriority::Low < CookiePriority::Medium);
+        assert!(CookiePriority::Medium < CookiePriority::High);
+        assert!(CookiePriority::default().is_default());
+    }
+}
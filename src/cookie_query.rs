@@ -0,0 +1,363 @@
+use cookie::SameSite;
+use time::{Duration, OffsetDateTime};
+
+use crate::cookie::Cookie;
+use crate::cookie_domain::{canonical_domain, is_same_or_subdomain};
+use crate::cookie_expiration::CookieExpiration;
+
+/// A composable selection of cookies within a [`CookieStore`](crate::CookieStore), for use with
+/// [`CookieStore::select`](crate::CookieStore::select),
+/// [`CookieStore::remove_selected`](crate::CookieStore::remove_selected) and
+/// [`CookieStore::expire_selected`](crate::CookieStore::expire_selected). A cookie is selected
+/// only if it satisfies every filter set on the query; a new query selects all __unexpired__
+/// cookies.
+///
+/// ```
+/// # use cookie_store::CookieQuery;
+/// // auth cookies for corp.example and its subdomains which expire within a day
+/// let query = CookieQuery::new()
+///     .domain("corp.example")
+///     .include_subdomains(true)
+///     .name("*auth*")
+///     .expires_within(time::Duration::days(1));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CookieQuery {
+    domain: Option<String>,
+    include_subdomains: bool,
+    path_prefix: Option<String>,
+    name: Option<String>,
+    secure: Option<bool>,
+    http_only: Option<bool>,
+    same_site: Option<SameSite>,
+    persistent: Option<bool>,
+    expires_after: Option<OffsetDateTime>,
+    expires_before: Option<OffsetDateTime>,
+    expires_within: Option<Duration>,
+    include_expired: bool,
+}
+
+impl CookieQuery {
+    pub fn new() -> CookieQuery {
+        CookieQuery::default()
+    }
+
+    /// Select cookies whose domain is `domain`; see also [`CookieQuery::include_subdomains`]
+    pub fn domain(self, domain: &str) -> CookieQuery {
+        CookieQuery {
+            domain: Some(canonical_domain(domain)),
+            ..self
+        }
+    }
+
+    /// If `include`, the [`CookieQuery::domain`] filter also selects cookies whose domain is a
+    /// subdomain of it
+    pub fn include_subdomains(self, include: bool) -> CookieQuery {
+        CookieQuery {
+            include_subdomains: include,
+            ..self
+        }
+    }
+
+    /// Select cookies whose path begins with `prefix`
+    pub fn path_prefix(self, prefix: &str) -> CookieQuery {
+        CookieQuery {
+            path_prefix: Some(prefix.to_owned()),
+            ..self
+        }
+    }
+
+    /// Select cookies whose name matches `pattern`, in which `*` matches any sequence of
+    /// characters
+    pub fn name(self, pattern: &str) -> CookieQuery {
+        CookieQuery {
+            name: Some(pattern.to_owned()),
+            ..self
+        }
+    }
+
+    /// Select cookies whose Secure attribute is (or is not) set
+    pub fn secure(self, secure: bool) -> CookieQuery {
+        CookieQuery {
+            secure: Some(secure),
+            ..self
+        }
+    }
+
+    /// Select cookies whose HttpOnly attribute is (or is not) set
+    pub fn http_only(self, http_only: bool) -> CookieQuery {
+        CookieQuery {
+            http_only: Some(http_only),
+            ..self
+        }
+    }
+
+    /// Select cookies with the SameSite attribute `same_site`
+    pub fn same_site(self, same_site: SameSite) -> CookieQuery {
+        CookieQuery {
+            same_site: Some(same_site),
+            ..self
+        }
+    }
+
+    /// Select __persistent__ (or __non-persistent__) cookies
+    pub fn persistent(self, persistent: bool) -> CookieQuery {
+        CookieQuery {
+            persistent: Some(persistent),
+            ..self
+        }
+    }
+
+    /// Select __persistent__ cookies expiring at or after `utc_tm`
+    pub fn expires_after(self, utc_tm: OffsetDateTime) -> CookieQuery {
+        CookieQuery {
+            expires_after: Some(utc_tm),
+            ..self
+        }
+    }
+
+    /// Select __persistent__ cookies expiring before `utc_tm`
+    pub fn expires_before(self, utc_tm: OffsetDateTime) -> CookieQuery {
+        CookieQuery {
+            expires_before: Some(utc_tm),
+            ..self
+        }
+    }
+
+    /// Select __persistent__ cookies expiring before `duration` from the time the query is
+    /// evaluated
+    pub fn expires_within(self, duration: Duration) -> CookieQuery {
+        CookieQuery {
+            expires_within: Some(duration),
+            ..self
+        }
+    }
+
+    /// If `include`, __expired__ cookies may also be selected
+    pub fn include_expired(self, include: bool) -> CookieQuery {
+        CookieQuery {
+            include_expired: include,
+            ..self
+        }
+    }
+
+    /// Returns true if `cookie` satisfies every filter of this query as of the current time, per
+    /// the system clock; to consult the [`Clock`](crate::Clock) of a store, use
+    /// [`matches_at`](CookieQuery::matches_at)`(cookie, store.now())`. The selection methods of
+    /// [`CookieStore`](crate::CookieStore) consult the store's clock.
+    pub fn matches(&self, cookie: &Cookie<'_>) -> bool {
+        self.matches_at(cookie, OffsetDateTime::now_utc())
+    }
+
+    /// Returns true if `cookie` satisfies every filter of this query as of `utc_tm`
+    pub fn matches_at(&self, cookie: &Cookie<'_>, utc_tm: OffsetDateTime) -> bool {
+        self.matches_domain(cookie)
+            && self
+                .path_prefix
+                .as_ref()
+                .map_or(true, |prefix| cookie.path.starts_with(prefix.as_str()))
+            && self
+                .name
+                .as_ref()
+                .map_or(true, |pattern| glob_match(pattern, cookie.name()))
+            && self
+                .secure
+                .map_or(true, |secure| cookie.secure().unwrap_or(false) == secure)
+            && self.http_only.map_or(true, |http_only| {
+                cookie.http_only().unwrap_or(false) == http_only
+            })
+            && self
+                .same_site
+                .map_or(true, |same_site| cookie.same_site() == Some(same_site))
+            && self
+                .persistent
+                .map_or(true, |persistent| cookie.is_persistent() == persistent)
+            && self.matches_expiry(&cookie.expires, utc_tm)
+            && (self.include_expired || !cookie.expires_by(&utc_tm))
+    }
+
+    fn matches_domain(&self, cookie: &Cookie<'_>) -> bool {
+        match (self.domain.as_ref(), cookie.domain.as_cow()) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(domain), Some(cookie_domain)) => {
+                if self.include_subdomains {
+                    is_same_or_subdomain(&cookie_domain, domain)
+                } else {
+                    cookie_domain == domain.as_str()
+                }
+            }
+        }
+    }
+
+    fn matches_expiry(&self, expires: &CookieExpiration, now: OffsetDateTime) -> bool {
+        if self.expires_after.is_none()
+            && self.expires_before.is_none()
+            && self.expires_within.is_none()
+        {
+            return true;
+        }
+        match *expires {
+            CookieExpiration::AtUtc(ref utc_tm) => {
+                self.expires_after.map_or(true, |after| *utc_tm >= after)
+                    && self.expires_before.map_or(true, |before| *utc_tm < before)
+                    && self
+                        .expires_within
+                        .and_then(|within| now.checked_add(within))
+                        .map_or(true, |until| *utc_tm < until)
+            }
+            CookieExpiration::SessionEnd => false,
+        }
+    }
+}
+
+/// Match `s` against `pattern`, in which `*` matches any (possibly empty) sequence of characters
+fn glob_match(pattern: &str, s: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // split always yields at least one part
+    let first = parts.next().unwrap_or("");
+    let mut rest = match s.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let mut parts = parts.peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            // the final part must match the end of the string
+            return rest.len() >= part.len() && rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    // no `*` in the pattern; require an exact match
+    rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use cookie::SameSite;
+    use time::Duration;
+
+    use super::{glob_match, CookieQuery};
+    use crate::utils::test as test_utils;
+    use crate::CookieStore;
+
+    #[test]
+    fn glob() {
+        assert!(glob_match("auth", "auth"));
+        assert!(!glob_match("auth", "oauth"));
+        assert!(!glob_match("auth", "auth2"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*auth*", "oauth_token"));
+        assert!(glob_match("auth*", "auth_token"));
+        assert!(!glob_match("auth*", "oauth_token"));
+        assert!(glob_match("*token", "oauth_token"));
+        assert!(glob_match("a*b*c", "abbc"));
+        assert!(!glob_match("a*b*c", "acb"));
+        assert!(!glob_match("ab*ba", "aba"));
+    }
+
+    fn store() -> CookieStore {
+        test_utils::store(&[
+            (
+                "auth=1; Max-Age=3600; Secure; HttpOnly",
+                "https://corp.example/",
+            ),
+            (
+                "oauth_token=2; Max-Age=172800",
+                "https://sso.corp.example/login/",
+            ),
+            (
+                "session_auth=3; Domain=corp.example",
+                "https://www.corp.example/",
+            ),
+            (
+                "pref=4; Max-Age=3600; SameSite=Strict",
+                "https://www.corp.example/app/",
+            ),
+            ("auth=5; Max-Age=3600", "https://corp.example.org/"),
+        ])
+    }
+
+    #[test]
+    fn filters() {
+        let store = store();
+        let selected = |query: &CookieQuery| test_utils::values(store.select(query));
+        assert_eq!(5, selected(&CookieQuery::new()).len());
+        assert_eq!(
+            vec!["auth=1", "session_auth=3"],
+            selected(&CookieQuery::new().domain(".CORP.example"))
+        );
+        assert_eq!(
+            vec!["auth=1", "oauth_token=2", "pref=4", "session_auth=3"],
+            selected(
+                &CookieQuery::new()
+                    .domain("corp.example")
+                    .include_subdomains(true)
+            )
+        );
+        assert_eq!(
+            vec!["pref=4"],
+            selected(&CookieQuery::new().path_prefix("/app"))
+        );
+        assert_eq!(
+            vec!["auth=1", "auth=5", "oauth_token=2", "session_auth=3"],
+            selected(&CookieQuery::new().name("*auth*"))
+        );
+        assert_eq!(
+            vec!["auth=1"],
+            selected(&CookieQuery::new().secure(true).http_only(true))
+        );
+        assert_eq!(
+            vec!["pref=4"],
+            selected(&CookieQuery::new().same_site(SameSite::Strict))
+        );
+        assert_eq!(
+            vec!["session_auth=3"],
+            selected(&CookieQuery::new().persistent(false))
+        );
+        assert_eq!(
+            vec!["auth=1"],
+            selected(
+                &CookieQuery::new()
+                    .domain("corp.example")
+                    .include_subdomains(true)
+                    .name("*auth*")
+                    .expires_within(Duration::days(1))
+            )
+        );
+        assert_eq!(
+            vec!["oauth_token=2"],
+            selected(&CookieQuery::new().expires_after(test_utils::in_days(1)))
+        );
+    }
+
+    #[test]
+    fn expires_within_large_duration() {
+        let store = store();
+        assert_eq!(
+            vec!["auth=1", "auth=5", "oauth_token=2", "pref=4"],
+            test_utils::values(store.select(&CookieQuery::new().expires_within(Duration::MAX)))
+        );
+    }
+
+    #[test]
+    fn remove_and_expire() {
+        let mut store = store();
+        let query = CookieQuery::new().name("auth");
+        assert_eq!(
+            vec!["auth=1", "auth=5"],
+            test_utils::values(store.remove_selected(&query))
+        );
+        assert!(store.get_any("corp.example", "/", "auth").is_none());
+        assert_eq!(3, store.iter_any().count());
+
+        let query = CookieQuery::new().domain("www.corp.example");
+        assert_eq!(1, store.expire_selected(&query));
+        assert_eq!(0, store.select(&query).count());
+        assert_eq!(1, store.select(&query.include_expired(true)).count());
+        assert_eq!(2, store.iter_unexpired().count());
+    }
+}
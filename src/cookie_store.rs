@@ -1,15 +1,34 @@
+use std::fmt;
 use std::io::{BufRead, Write};
 use std::ops::Deref;
+use std::sync::Arc;
 
 use cookie::Cookie as RawCookie;
 use log::debug;
+use time::OffsetDateTime;
 use url::Url;
 
+use crate::clock::Clock;
 use crate::cookie::Cookie;
-use crate::cookie_domain::is_match as domain_match;
+use crate::cookie_domain::match_candidates;
+#[cfg(feature = "public_suffix")]
+use crate::cookie_domain::registrable_domain;
+use crate::cookie_domain::{
+    approximate_registrable_domain, canonical_domain, is_same_or_subdomain,
+};
 use crate::cookie_path::is_match as path_match;
+use crate::cookie_policy::CookiePolicy;
+use crate::cookie_query::CookieQuery;
+use crate::expiry_index::ExpiryIndex;
+use crate::store_diff::StoreDiff;
+use crate::store_error::{check_loaded, StoreError};
+use crate::store_merge::{MergeChoice, MergeConflict, MergeReport, MergeStrategy};
+use crate::store_observer::{ChangeCause, ObserverId, Observers, StoreChange};
+use crate::store_report::{SetCookieOutcome, SetCookieReport};
+use crate::store_stats::StoreStats;
+use crate::store_transaction::{Transaction, UndoLog};
 use crate::utils::{is_http_scheme, is_secure};
-use crate::CookieError;
+use crate::{CookieError, CookieErrorKind};
 
 #[cfg(feature = "preserve_order")]
 use indexmap::IndexMap;
@@ -34,9 +53,16 @@ pub enum StoreAction {
     UpdatedExisting,
 }
 
-pub type StoreResult<T> = Result<T, crate::Error>;
+pub type StoreResult<T> = Result<T, StoreError>;
 pub type InsertResult = Result<StoreAction, CookieError>;
 
+/// A `CookieError` of `kind` for `cookie`, received from `request_url`
+fn rejection(kind: CookieErrorKind, cookie: &Cookie<'_>, request_url: &Url) -> CookieError {
+    CookieError::from(kind)
+        .with_cookie(cookie)
+        .with_request_url(request_url)
+}
+
 #[derive(Debug, Default, Clone)]
 /// An implementation for storing and retrieving [`Cookie`]s per the path and domain matching
 /// rules specified in [RFC6265](https://datatracker.ietf.org/doc/html/rfc6265).
@@ -45,9 +71,25 @@ pub struct CookieStore {
     cookies: DomainMap,
     #[cfg(feature = "public_suffix")]
     /// If set, enables [public suffix](https://datatracker.ietf.org/doc/html/rfc6265#section-5.3) rejection based on the provided `publicsuffix::List`
-    public_suffix_list: Option<publicsuffix::List>,
+    public_suffix_list: Option<Arc<publicsuffix::List>>,
+    /// If set, consulted when storing and returning cookies, in addition to the RFC6265 rules
+    policy: Option<Arc<dyn CookiePolicy>>,
+    /// If set, enables third-party cookie blocking, except in the context of the contained sites
+    third_party_exceptions: Option<Vec<String>>,
+    /// If set, the source of the current time; otherwise the system clock is used
+    clock: Option<Arc<dyn Clock>>,
+    /// If set, the number of cookies kept per domain, beyond which cookies are evicted
+    max_cookies_per_domain: Option<usize>,
+    /// Callbacks notified of each change to the store
+    observers: Observers,
+    /// Changes made within any open transaction
+    pub(crate) undo_log: UndoLog,
+    /// The __persistent__ cookies of `cookies`, ordered by expiry time
+    expiry_index: ExpiryIndex,
 }
 
+// Each public method which changes the store must be forwarded by `Transaction` (see
+// store_transaction.rs), and exercised by its `forwarded_methods` test
 impl CookieStore {
     #[deprecated(
         since = "0.14.1",
@@ -57,24 +99,103 @@ impl CookieStore {
     /// HTTP request. As the items are intended for use in creating a `Cookie` header in a GET request,
     /// they may contain only the `name` and `value` of a received cookie, eliding other parameters
     /// such as `path` or `expires`. For iteration over `Cookie` instances containing all data, please
-    /// refer to [`CookieStore::matches`].
+    /// refer to [`CookieStore::matches`]. As the returned `Iterator` does not borrow `url`, the
+    /// matching cookies are collected first; to avoid this allocation, see
+    /// [`CookieStore::iter_matches`] and [`CookieStore::write_cookie_header`].
     pub fn get_request_cookies(&self, url: &Url) -> impl Iterator<Item = &RawCookie<'static>> {
         self.matches(url).into_iter().map(|c| c.deref())
     }
 
     /// Return an `Iterator` of the cookie (`name`, `value`) pairs for `url` in the store, suitable
     /// for use in the `Cookie` header of an HTTP request. For iteration over `Cookie` instances,
-    /// please refer to [`CookieStore::matches`].
+    /// please refer to [`CookieStore::matches`]. As the returned `Iterator` does not borrow `url`,
+    /// the matching cookies are collected first; to avoid this allocation, see
+    /// [`CookieStore::iter_matches`] and [`CookieStore::write_cookie_header`].
     pub fn get_request_values(&self, url: &Url) -> impl Iterator<Item = (&str, &str)> {
         self.matches(url).into_iter().map(|c| c.name_value())
     }
 
-    /// Store the `cookies` received from `url`
+    /// Store the `cookies` received from `url`. As `cookie::Cookie` does not retain the Priority
+    /// attribute, the cookies stored have `Medium` [priority](Cookie::priority); to honor the
+    /// attribute, store the Set-Cookie header text via
+    /// [`CookieStore::store_response_set_cookie_headers`].
     pub fn store_response_cookies<I: Iterator<Item = RawCookie<'static>>>(
         &mut self,
         cookies: I,
         url: &Url,
     ) {
+        self.store_response_cookies_in_context(cookies, url, None, None);
+    }
+
+    /// Store the cookies of the Set-Cookie header values `set_cookies` received from `url`.
+    /// Unlike [`CookieStore::store_response_cookies`], the header text is parsed here, so an
+    /// Expires attribute in a non-standard format is understood per RFC6265 (see
+    /// [`Cookie::parse_at`]), and the header and its extension attributes are retained. Headers
+    /// which fail to parse, or whose cookies are rejected, are ignored.
+    pub fn store_response_set_cookie_headers<I>(&mut self, set_cookies: I, url: &Url)
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let now = self.now();
+        for set_cookie in set_cookies {
+            let result = Cookie::parse_at(set_cookie.as_ref(), url, now)
+                .and_then(|cookie| self.insert_in_context(cookie.into_owned(), url, None, now));
+            if let Err(ref e) = result {
+                debug!("unable to store Set-Cookie: {e:?}");
+            }
+        }
+    }
+
+    /// Store the `cookies` received from `url` in the context of `top_level_site`; see
+    /// [`CookieStore::insert_for_site`]
+    pub fn store_response_cookies_for_site<I: Iterator<Item = RawCookie<'static>>>(
+        &mut self,
+        cookies: I,
+        url: &Url,
+        top_level_site: &Url,
+    ) {
+        self.store_response_cookies_in_context(cookies, url, Some(top_level_site), None);
+    }
+
+    /// As [`CookieStore::store_response_cookies`], returning how each of the `cookies` was
+    /// stored, or why it was rejected
+    pub fn store_response_cookies_with_report<I: Iterator<Item = RawCookie<'static>>>(
+        &mut self,
+        cookies: I,
+        url: &Url,
+    ) -> SetCookieReport {
+        let mut report = SetCookieReport::default();
+        self.store_response_cookies_in_context(cookies, url, None, Some(&mut report));
+        report
+    }
+
+    /// As [`CookieStore::store_response_cookies_for_site`], returning how each of the `cookies`
+    /// was stored, or why it was rejected
+    pub fn store_response_cookies_for_site_with_report<I: Iterator<Item = RawCookie<'static>>>(
+        &mut self,
+        cookies: I,
+        url: &Url,
+        top_level_site: &Url,
+    ) -> SetCookieReport {
+        let mut report = SetCookieReport::default();
+        self.store_response_cookies_in_context(
+            cookies,
+            url,
+            Some(top_level_site),
+            Some(&mut report),
+        );
+        report
+    }
+
+    fn store_response_cookies_in_context<I: Iterator<Item = RawCookie<'static>>>(
+        &mut self,
+        cookies: I,
+        url: &Url,
+        top_level_site: Option<&Url>,
+        mut report: Option<&mut SetCookieReport>,
+    ) {
+        let now = self.now();
         for cookie in cookies {
             if cookie.secure() != Some(true) || cfg!(feature = "log_secure_cookie_values") {
                 debug!("inserting Set-Cookie '{cookie:?}'");
@@ -82,9 +203,15 @@ impl CookieStore {
                 debug!("inserting secure cookie '{}'", cookie.name());
             }
 
-            if let Err(e) = self.insert_raw(&cookie, url) {
+            let result = Cookie::try_from_raw_cookie_at(&cookie, url, now).and_then(|cookie| {
+                self.insert_in_context(cookie.into_owned(), url, top_level_site, now)
+            });
+            if let Err(ref e) = result {
                 debug!("unable to store Set-Cookie: {e:?}");
             }
+            if let Some(ref mut report) = report {
+                report.outcomes.push(SetCookieOutcome { cookie, result });
+            }
         }
     }
 
@@ -92,12 +219,146 @@ impl CookieStore {
     /// matching](https://datatracker.ietf.org/doc/html/rfc6265#section-5.3)
     #[cfg(feature = "public_suffix")]
     pub fn with_suffix_list(self, psl: publicsuffix::List) -> CookieStore {
+        CookieStore {
+            public_suffix_list: Some(Arc::new(psl)),
+            ..self
+        }
+    }
+
+    /// Specify a [`CookiePolicy`] for the `CookieStore` to consult when storing and returning
+    /// cookies, in addition to the rules of RFC6265.
+    pub fn with_policy<P: CookiePolicy + 'static>(self, policy: P) -> CookieStore {
+        CookieStore {
+            policy: Some(Arc::new(policy)),
+            ..self
+        }
+    }
+
+    /// Enable blocking of third-party cookies: cookies which are not same-site with the
+    /// top-level site passed to [`CookieStore::insert_for_site`] are rejected, and those not
+    /// same-site with the top-level site passed to [`CookieStore::matches_for_site`] are
+    /// withheld. Third-party cookies remain allowed in the context of the `exceptions` sites.
+    ///
+    /// Sites are compared by registrable domain per the public suffix list, if one is
+    /// configured. Otherwise, a cookie domain is considered same-site if it is identical to, a
+    /// subdomain of, or a parent domain of the top-level site host, or if the two share their
+    /// last two labels (e.g. `a.example.com` and `b.example.com`); note that this treats sites
+    /// under a multi-label public suffix, such as `a.co.uk` and `b.co.uk`, as same-site.
+    pub fn with_third_party_blocking<I, S>(self, exceptions: I) -> CookieStore
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        CookieStore {
+            third_party_exceptions: Some(
+                exceptions
+                    .into_iter()
+                    .map(|e| e.as_ref().trim().to_lowercase())
+                    .collect(),
+            ),
+            ..self
+        }
+    }
+
+    /// Specify the [`Clock`] the `CookieStore` consults for the current time, e.g. a
+    /// [`ManualClock`](crate::ManualClock) for deterministic handling of expiration. The clock is
+    /// shared by clones of the store.
+    pub fn with_clock<C: Clock + 'static>(self, clock: C) -> CookieStore {
+        CookieStore {
+            clock: Some(Arc::new(clock)),
+            ..self
+        }
+    }
+
+    /// Limit the number of cookies kept per domain (the domain-attribute, or the host of a
+    /// host-only cookie) to `max`, as browsers do. When inserting a new cookie into a full domain,
+    /// other cookies of the domain are evicted: __expired__ cookies first, then those of the
+    /// lowest [`CookiePriority`](crate::CookiePriority), then the least recently created.
+    /// Evictions are reported to observers with [`ChangeCause::Evicted`]. The limit is applied
+    /// when cookies are inserted (e.g. via [`CookieStore::insert`] or
+    /// [`CookieStore::store_response_cookies`]), not when they are loaded or merged.
+    pub fn with_max_cookies_per_domain(self, max: usize) -> CookieStore {
+        CookieStore {
+            max_cookies_per_domain: Some(max),
+            ..self
+        }
+    }
+
+    /// The cookies of this store, with the configuration (public suffix list, policy,
+    /// third-party blocking, clock, and cookie limit) of `template`, and without observers
+    pub(crate) fn with_config_of(self, template: &CookieStore) -> CookieStore {
         CookieStore {
             cookies: self.cookies,
-            public_suffix_list: Some(psl),
+            expiry_index: self.expiry_index,
+            ..template.empty()
+        }
+    }
+
+    /// An empty store with the configuration of this store, and without observers
+    pub(crate) fn empty(&self) -> CookieStore {
+        CookieStore {
+            #[cfg(feature = "public_suffix")]
+            public_suffix_list: self.public_suffix_list.clone(),
+            policy: self.policy.clone(),
+            third_party_exceptions: self.third_party_exceptions.clone(),
+            clock: self.clock.clone(),
+            max_cookies_per_domain: self.max_cookies_per_domain,
+            ..CookieStore::new()
         }
     }
 
+    /// The current time, per the store's [`Clock`] if one was set via
+    /// [`CookieStore::with_clock`], otherwise the system clock
+    pub fn now(&self) -> OffsetDateTime {
+        self.clock
+            .as_ref()
+            .map_or_else(OffsetDateTime::now_utc, |clock| clock.now_utc())
+    }
+
+    /// Returns true if `a` and `b` are hosts of the same site
+    fn is_same_site(&self, a: &str, b: &str) -> bool {
+        #[cfg(feature = "public_suffix")]
+        if let Some(ref psl) = self.public_suffix_list {
+            return registrable_domain(a, psl) == registrable_domain(b, psl);
+        }
+        is_same_or_subdomain(a, b)
+            || is_same_or_subdomain(b, a)
+            || approximate_registrable_domain(a) == approximate_registrable_domain(b)
+    }
+
+    /// Returns true if third-party cookie blocking is enabled and `cookie` is third-party to
+    /// `top_level_site`, which is not an exception
+    fn is_blocked_third_party(&self, cookie: &Cookie<'_>, top_level_site: &Url) -> bool {
+        match self.third_party_exceptions {
+            None => false,
+            Some(ref exceptions) => match (top_level_site.host_str(), cookie.domain.as_cow()) {
+                (Some(site), Some(domain)) => {
+                    !self.is_same_site(&domain, site)
+                        && !exceptions.iter().any(|e| self.is_same_site(e, site))
+                }
+                _ => true,
+            },
+        }
+    }
+
+    /// Register `observer` to be called with a [`StoreChange`] for every change subsequently made
+    /// to the store, whether via [`CookieStore::insert`] (and the methods built upon it, such as
+    /// [`CookieStore::store_response_cookies`]), [`CookieStore::remove`], or
+    /// [`CookieStore::clear`]. A clone of the store is notified by the observers registered at the
+    /// time it was cloned; observers subsequently added to or removed from either store do not
+    /// affect the other.
+    pub fn add_observer<F>(&mut self, observer: F) -> ObserverId
+    where
+        F: Fn(&StoreChange<'_>) + Send + Sync + 'static,
+    {
+        self.observers.add(Arc::new(observer))
+    }
+
+    /// Unregister the observer identified by `id`, returning true if it was registered
+    pub fn remove_observer(&mut self, id: ObserverId) -> bool {
+        self.observers.remove(id)
+    }
+
     /// Returns true if the `CookieStore` contains an __unexpired__ `Cookie` corresponding to the
     /// specified `domain`, `path`, and `name`.
     pub fn contains(&self, domain: &str, path: &str, name: &str) -> bool {
@@ -113,20 +374,9 @@ impl CookieStore {
     /// Returns a reference to the __unexpired__ `Cookie` corresponding to the specified `domain`,
     /// `path`, and `name`.
     pub fn get(&self, domain: &str, path: &str, name: &str) -> Option<&Cookie<'_>> {
+        let now = self.now();
         self.get_any(domain, path, name).and_then(|cookie| {
-            if cookie.is_expired() {
-                None
-            } else {
-                Some(cookie)
-            }
-        })
-    }
-
-    /// Returns a mutable reference to the __unexpired__ `Cookie` corresponding to the specified
-    /// `domain`, `path`, and `name`.
-    fn get_mut(&mut self, domain: &str, path: &str, name: &str) -> Option<&mut Cookie<'static>> {
-        self.get_mut_any(domain, path, name).and_then(|cookie| {
-            if cookie.is_expired() {
+            if cookie.expires_by(&now) {
                 None
             } else {
                 Some(cookie)
@@ -144,8 +394,18 @@ impl CookieStore {
         })
     }
 
+    /// Returns a mutable reference to the __unexpired__ `Cookie` corresponding to the specified
+    /// `domain`, `path`, and `name`.
+    #[cfg(test)]
+    fn get_mut(&mut self, domain: &str, path: &str, name: &str) -> Option<&mut Cookie<'static>> {
+        let now = self.now();
+        self.get_mut_any(domain, path, name)
+            .filter(|cookie| !cookie.expires_by(&now))
+    }
+
     /// Returns a mutable reference to the (possibly __expired__) `Cookie` corresponding to the
-    /// specified `domain`, `path`, and `name`.
+    /// specified `domain`, `path`, and `name`. Any change to its expiry must be reflected in the
+    /// `expiry_index`.
     fn get_mut_any(
         &mut self,
         domain: &str,
@@ -161,6 +421,38 @@ impl CookieStore {
 
     /// Removes a `Cookie` from the store, returning the `Cookie` if it was in the store
     pub fn remove(&mut self, domain: &str, path: &str, name: &str) -> Option<Cookie<'static>> {
+        self.remove_with_cause(domain, path, name, None, ChangeCause::Removed)
+    }
+
+    /// As [`CookieStore::remove`], notifying observers of the removal with `cause`
+    fn remove_with_cause(
+        &mut self,
+        domain: &str,
+        path: &str,
+        name: &str,
+        request_url: Option<&Url>,
+        cause: ChangeCause,
+    ) -> Option<Cookie<'static>> {
+        let removed = self.take_cookie(domain, path, name);
+
+        if let Some(ref old) = removed {
+            self.observers.notify(StoreChange {
+                cause,
+                request_url,
+                old: Some(old),
+                new: None,
+            });
+            if self.undo_log.is_active() {
+                self.undo_log.record(domain, path, name, Some(old.clone()));
+            }
+        }
+
+        removed
+    }
+
+    /// Remove the `Cookie` under `domain`, `path`, and `name` from the map, along with any
+    /// emptied `PathMap`/`NameMap`, without recording or notifying the change
+    fn take_cookie(&mut self, domain: &str, path: &str, name: &str) -> Option<Cookie<'static>> {
         #[cfg(not(feature = "preserve_order"))]
         fn map_remove<K, V, Q>(map: &mut Map<K, V>, key: &Q) -> Option<V>
         where
@@ -201,6 +493,9 @@ impl CookieStore {
         if remove_domain {
             map_remove(&mut self.cookies, domain);
         }
+        if let Some(ref removed) = removed {
+            self.expiry_index.remove(removed);
+        }
 
         removed
     }
@@ -209,46 +504,171 @@ impl CookieStore {
     /// `request_url`, as well as having HttpOnly and Secure attributes compatible with the
     /// `request_url`.
     pub fn matches(&self, request_url: &Url) -> Vec<&Cookie<'static>> {
-        // although we domain_match and path_match as we descend through the tree, we
-        // still need to
-        // do a full Cookie::matches() check in the last filter. Otherwise, we cannot
-        // properly deal
-        // with HostOnly Cookies.
-        let cookies = self
-            .cookies
-            .iter()
-            .filter(|&(d, _)| domain_match(d, request_url))
-            .flat_map(|(_, dcs)| {
+        self.matches_in_context(request_url, None, self.now())
+    }
+
+    /// As [`CookieStore::matches`], with cookies considered __expired__ as of `utc_tm` rather
+    /// than the current time
+    pub fn matches_at(&self, request_url: &Url, utc_tm: OffsetDateTime) -> Vec<&Cookie<'static>> {
+        self.matches_in_context(request_url, None, utc_tm)
+    }
+
+    /// As [`CookieStore::matches`], for a request made in the context of `top_level_site` (the
+    /// URL of the top-level document). If third-party cookie blocking is enabled via
+    /// [`CookieStore::with_third_party_blocking`], cookies which are not same-site with
+    /// `top_level_site` are withheld.
+    pub fn matches_for_site(
+        &self,
+        request_url: &Url,
+        top_level_site: &Url,
+    ) -> Vec<&Cookie<'static>> {
+        self.matches_in_context(request_url, Some(top_level_site), self.now())
+    }
+
+    fn matches_in_context(
+        &self,
+        request_url: &Url,
+        top_level_site: Option<&Url>,
+        now: OffsetDateTime,
+    ) -> Vec<&Cookie<'static>> {
+        self.iter_matches_in_context(request_url, top_level_site, now)
+            .collect()
+    }
+
+    /// A lazy iterator over the __unexpired__ cookies that match `request_url`, as returned by
+    /// [`CookieStore::matches`], without collecting them into a `Vec`
+    pub fn iter_matches<'a>(
+        &'a self,
+        request_url: &'a Url,
+    ) -> impl Iterator<Item = &'a Cookie<'static>> + 'a {
+        self.iter_matches_in_context(request_url, None, self.now())
+    }
+
+    /// As [`CookieStore::iter_matches`], for a request made in the context of `top_level_site`;
+    /// see [`CookieStore::matches_for_site`]
+    pub fn iter_matches_for_site<'a>(
+        &'a self,
+        request_url: &'a Url,
+        top_level_site: &'a Url,
+    ) -> impl Iterator<Item = &'a Cookie<'static>> + 'a {
+        self.iter_matches_in_context(request_url, Some(top_level_site), self.now())
+    }
+
+    fn iter_matches_in_context<'s: 'u, 'u>(
+        &'s self,
+        request_url: &'u Url,
+        top_level_site: Option<&'u Url>,
+        now: OffsetDateTime,
+    ) -> impl Iterator<Item = &'s Cookie<'static>> + 'u {
+        let http = is_http_scheme(request_url);
+        let secure = is_secure(request_url);
+        // Cookies are stored under their canonicalized domain, so only the request-host and its
+        // parent domains need be visited. Although we domain_match and path_match as we descend
+        // through the tree, we still need to do a full Cookie::matches() check in the last filter.
+        // Otherwise, we cannot properly deal with HostOnly Cookies.
+        let request_path = request_url.path();
+        request_url
+            .host_str()
+            .into_iter()
+            .flat_map(match_candidates)
+            .filter_map(move |d| self.cookies.get(d))
+            .flat_map(move |dcs| {
                 dcs.iter()
-                    .filter(|&(p, _)| path_match(p, request_url))
-                    .flat_map(|(_, pcs)| {
-                        pcs.values()
-                            .filter(|c| !c.is_expired() && c.matches(request_url))
-                    })
-            });
-        match (!is_http_scheme(request_url), !is_secure(request_url)) {
-            (true, true) => cookies
-                .filter(|c| !c.http_only().unwrap_or(false) && !c.secure().unwrap_or(false))
-                .collect(),
-            (true, false) => cookies
-                .filter(|c| !c.http_only().unwrap_or(false))
-                .collect(),
-            (false, true) => cookies.filter(|c| !c.secure().unwrap_or(false)).collect(),
-            (false, false) => cookies.collect(),
+                    .filter(move |&(p, _)| path_match(p, request_path))
+                    .flat_map(|(_, pcs)| pcs.values())
+            })
+            .filter(move |c| {
+                (http || !c.http_only().unwrap_or(false))
+                    && (secure || !c.secure().unwrap_or(false))
+                    && !c.expires_by(&now)
+                    && c.matches(request_url)
+                    && self
+                        .policy
+                        .as_ref()
+                        .map_or(true, |policy| policy.return_ok(c, request_url))
+                    && !top_level_site.map_or(false, |site| self.is_blocked_third_party(c, site))
+            })
+    }
+
+    /// Write the value of the `Cookie` header for a request to `request_url`, i.e. the
+    /// `name=value` pairs of the cookies returned by [`CookieStore::matches`] separated by `"; "`,
+    /// to `writer`, without allocating. Returns the number of cookies written; if zero, the
+    /// request should carry no `Cookie` header.
+    pub fn write_cookie_header<W: fmt::Write>(
+        &self,
+        request_url: &Url,
+        writer: &mut W,
+    ) -> Result<usize, fmt::Error> {
+        let mut written = 0;
+        for cookie in self.iter_matches(request_url) {
+            if written > 0 {
+                writer.write_str("; ")?;
+            }
+            writer.write_str(cookie.name())?;
+            writer.write_char('=')?;
+            writer.write_str(cookie.value())?;
+            written += 1;
+        }
+        Ok(written)
+    }
+
+    /// As [`CookieStore::write_cookie_header`], writing at most `max_len` bytes. Should the
+    /// matching cookies not fit, cookies are admitted in order of decreasing
+    /// [`CookiePriority`](crate::CookiePriority), each if it still fits, so that lower priority
+    /// cookies are dropped first; those admitted are written in their usual order. Unlike
+    /// `write_cookie_header`, this collects the matching cookies.
+    pub fn write_cookie_header_within<W: fmt::Write>(
+        &self,
+        request_url: &Url,
+        max_len: usize,
+        writer: &mut W,
+    ) -> Result<usize, fmt::Error> {
+        let cookies = self.iter_matches(request_url).collect::<Vec<_>>();
+        let pair_len = |c: &Cookie<'_>| c.name().len() + 1 + c.value().len();
+        let mut by_priority = (0..cookies.len()).collect::<Vec<_>>();
+        // stable, so cookies of equal priority are admitted in their usual order
+        by_priority.sort_by_key(|&i| std::cmp::Reverse(cookies[i].priority()));
+        let mut admitted = vec![false; cookies.len()];
+        let mut len = 0;
+        for i in by_priority {
+            let separator_len = if len > 0 { 2 } else { 0 };
+            let added_len = separator_len + pair_len(cookies[i]);
+            if len + added_len <= max_len {
+                admitted[i] = true;
+                len += added_len;
+            }
+        }
+        let mut written = 0;
+        for cookie in cookies
+            .iter()
+            .zip(admitted)
+            .filter(|(_, a)| *a)
+            .map(|(c, _)| c)
+        {
+            if written > 0 {
+                writer.write_str("; ")?;
+            }
+            writer.write_str(cookie.name())?;
+            writer.write_char('=')?;
+            writer.write_str(cookie.value())?;
+            written += 1;
         }
+        Ok(written)
     }
 
     /// Parses a new `Cookie` from `cookie_str` and inserts it into the store.
     pub fn parse(&mut self, cookie_str: &str, request_url: &Url) -> InsertResult {
-        Cookie::parse(cookie_str, request_url)
-            .and_then(|cookie| self.insert(cookie.into_owned(), request_url))
+        let now = self.now();
+        Cookie::parse_at(cookie_str, request_url, now)
+            .and_then(|cookie| self.insert_at(cookie.into_owned(), request_url, now))
     }
 
     /// Converts a `cookie::Cookie` (from the `cookie` crate) into a `cookie_store::Cookie` and
     /// inserts it into the store.
     pub fn insert_raw(&mut self, cookie: &RawCookie<'_>, request_url: &Url) -> InsertResult {
-        Cookie::try_from_raw_cookie(cookie, request_url)
-            .and_then(|cookie| self.insert(cookie.into_owned(), request_url))
+        let now = self.now();
+        Cookie::try_from_raw_cookie_at(cookie, request_url, now)
+            .and_then(|cookie| self.insert_at(cookie.into_owned(), request_url, now))
     }
 
     /// Inserts `cookie`, received from `request_url`, into the store, following the rules of the
@@ -258,15 +678,53 @@ impl CookieStore {
     /// `Cookie` in the store, the existing `Cookie` wil be `expired()` and
     /// `Ok(StoreAction::ExpiredExisting)` will be returned.
     pub fn insert(&mut self, cookie: Cookie<'static>, request_url: &Url) -> InsertResult {
+        let now = self.now();
+        self.insert_in_context(cookie, request_url, None, now)
+    }
+
+    /// As [`CookieStore::insert`], with cookies (both `cookie` and any existing entry it
+    /// replaces) considered __expired__ as of `utc_tm` rather than the current time
+    pub fn insert_at(
+        &mut self,
+        cookie: Cookie<'static>,
+        request_url: &Url,
+        utc_tm: OffsetDateTime,
+    ) -> InsertResult {
+        self.insert_in_context(cookie, request_url, None, utc_tm)
+    }
+
+    /// As [`CookieStore::insert`], for a response received in the context of `top_level_site`
+    /// (the URL of the top-level document). If third-party cookie blocking is enabled via
+    /// [`CookieStore::with_third_party_blocking`], a cookie which is not same-site with
+    /// `top_level_site` is rejected with `CookieErrorKind::ThirdParty`.
+    pub fn insert_for_site(
+        &mut self,
+        cookie: Cookie<'static>,
+        request_url: &Url,
+        top_level_site: &Url,
+    ) -> InsertResult {
+        let now = self.now();
+        self.insert_in_context(cookie, request_url, Some(top_level_site), now)
+    }
+
+    fn insert_in_context(
+        &mut self,
+        mut cookie: Cookie<'static>,
+        request_url: &Url,
+        top_level_site: Option<&Url>,
+        now: OffsetDateTime,
+    ) -> InsertResult {
         if cookie.http_only().unwrap_or(false) && !is_http_scheme(request_url) {
             // If the cookie was received from a "non-HTTP" API and the
             // cookie's http-only-flag is set, abort these steps and ignore the
             // cookie entirely.
-            return Err(CookieError::NonHttpScheme);
+            return Err(rejection(
+                CookieErrorKind::NonHttpScheme,
+                &cookie,
+                request_url,
+            ));
         }
         #[cfg(feature = "public_suffix")]
-        let mut cookie = cookie;
-        #[cfg(feature = "public_suffix")]
         if let Some(ref psl) = self.public_suffix_list {
             // If the user agent is configured to reject "public suffixes"
             if cookie.domain.is_public_suffix(psl) {
@@ -277,11 +735,16 @@ impl CookieStore {
                     //     Let the domain-attribute be the empty string.
                     // (NB: at this point, an empty domain-attribute should be represented
                     // as the HostOnly variant of CookieDomain)
-                    cookie.domain = crate::cookie_domain::CookieDomain::host_only(request_url)?;
+                    cookie.domain = crate::cookie_domain::CookieDomain::host_only(request_url)
+                        .map_err(|e| e.with_cookie(&cookie).with_request_url(request_url))?;
                 } else {
                     //   Otherwise:
                     //     Ignore the cookie entirely and abort these steps.
-                    return Err(CookieError::PublicSuffix);
+                    return Err(rejection(
+                        CookieErrorKind::PublicSuffix,
+                        &cookie,
+                        request_url,
+                    ));
                 }
             }
         }
@@ -289,66 +752,271 @@ impl CookieStore {
             // If the canonicalized request-host does not domain-match the
             // domain-attribute:
             //    Ignore the cookie entirely and abort these steps.
-            return Err(CookieError::DomainMismatch);
+            return Err(rejection(
+                CookieErrorKind::DomainMismatch,
+                &cookie,
+                request_url,
+            ));
+        }
+        if let Some(ref policy) = self.policy {
+            if !policy.set_ok(&cookie, request_url) {
+                return Err(rejection(
+                    CookieErrorKind::PolicyRejected,
+                    &cookie,
+                    request_url,
+                ));
+            }
+        }
+        if let Some(site) = top_level_site {
+            if self.is_blocked_third_party(&cookie, site) {
+                return Err(rejection(CookieErrorKind::ThirdParty, &cookie, request_url));
+            }
         }
         // NB: we do not bail out above on is_expired(), as servers can remove a cookie
         // by sending
         // an expired one, so we need to do the old_cookie check below before checking
         // is_expired() on an incoming cookie
 
+        let observed = !self.observers.is_empty();
+        // Set the cookie's creation-time to the current date and time, unless it replaces an
+        // old-cookie, in which case its creation-time is retained
+        let mut creation_time = now;
         {
             // At this point in parsing, any non-present Domain attribute should have been
             // converted into a HostOnly variant
-            let cookie_domain = cookie
-                .domain
-                .as_cow()
-                .ok_or(CookieError::UnspecifiedDomain)?;
-            if let Some(old_cookie) = self.get_mut(&cookie_domain, &cookie.path, cookie.name()) {
-                if old_cookie.http_only().unwrap_or(false) && !is_http_scheme(request_url) {
+            let cookie_domain = cookie.domain.as_cow().ok_or_else(|| {
+                rejection(CookieErrorKind::UnspecifiedDomain, &cookie, request_url)
+            })?;
+            let old_cookie = self
+                .get_any(&cookie_domain, &cookie.path, cookie.name())
+                .filter(|c| !c.expires_by(&now))
+                .map(|c| (c.http_only().unwrap_or(false), c.creation_time()));
+            if let Some((old_http_only, old_creation_time)) = old_cookie {
+                if old_http_only && !is_http_scheme(request_url) {
                     // 2.  If the newly created cookie was received from a "non-HTTP"
                     //    API and the old-cookie's http-only-flag is set, abort these
                     //    steps and ignore the newly created cookie entirely.
-                    return Err(CookieError::NonHttpScheme);
-                } else if cookie.is_expired() {
-                    old_cookie.expire();
+                    return Err(rejection(
+                        CookieErrorKind::NonHttpScheme,
+                        &cookie,
+                        request_url,
+                    ));
+                } else if cookie.expires_by(&now) {
+                    self.expire_cookie(
+                        &cookie_domain,
+                        &cookie.path,
+                        cookie.name(),
+                        Some(request_url),
+                        ChangeCause::ExpiredExisting,
+                    );
                     return Ok(StoreAction::ExpiredExisting);
                 }
+                creation_time = old_creation_time.unwrap_or(now);
             }
         }
-
-        if !cookie.is_expired() {
-            Ok(
-                if self
-                    .cookies
-                    .entry(String::from(&cookie.domain))
-                    .or_default()
-                    .entry(String::from(&cookie.path))
-                    .or_default()
-                    .insert(cookie.name().to_owned(), cookie)
-                    .is_none()
-                {
-                    StoreAction::Inserted
-                } else {
-                    StoreAction::UpdatedExisting
-                },
-            )
+        cookie.set_creation_time(creation_time);
+
+        if !cookie.expires_by(&now) {
+            let key = if observed || self.max_cookies_per_domain.is_some() {
+                Some((
+                    String::from(&cookie.domain),
+                    String::from(&cookie.path),
+                    cookie.name().to_owned(),
+                ))
+            } else {
+                None
+            };
+            let old = self.store_cookie(cookie);
+            let action = if old.is_none() {
+                StoreAction::Inserted
+            } else {
+                StoreAction::UpdatedExisting
+            };
+            if let Some((domain, path, name)) = key {
+                self.observers.notify(StoreChange {
+                    cause: if old.is_none() {
+                        ChangeCause::Inserted
+                    } else {
+                        ChangeCause::UpdatedExisting
+                    },
+                    request_url: Some(request_url),
+                    old: old.as_ref(),
+                    new: self.get_any(&domain, &path, &name),
+                });
+                if old.is_none() {
+                    self.evict_excess(&domain, &path, &name, request_url, now);
+                }
+            }
+            Ok(action)
         } else {
-            Err(CookieError::Expired)
+            Err(rejection(CookieErrorKind::Expired, &cookie, request_url))
         }
     }
 
+    /// The (path, name) of each cookie of `domain` if a cookie limit is configured, i.e. those
+    /// which an insertion may evict
+    #[cfg(feature = "serde_json")]
+    pub(crate) fn evictable(&self, domain: &str) -> Option<Vec<(String, String)>> {
+        self.max_cookies_per_domain?;
+        Some(
+            self.cookies
+                .get(domain)
+                .map_or_else(Vec::new, |domain_cookies| {
+                    domain_cookies
+                        .iter()
+                        .flat_map(|(path, names)| {
+                            names.keys().map(move |name| (path.clone(), name.clone()))
+                        })
+                        .collect()
+                }),
+        )
+    }
+
+    /// Evict cookies of `domain` in excess of the configured maximum, other than the newly
+    /// inserted cookie under `path` and `name`; see [`CookieStore::with_max_cookies_per_domain`]
+    fn evict_excess(
+        &mut self,
+        domain: &str,
+        path: &str,
+        name: &str,
+        request_url: &Url,
+        now: OffsetDateTime,
+    ) {
+        let max = match self.max_cookies_per_domain {
+            Some(max) => max,
+            None => return,
+        };
+        let domain_cookies = match self.cookies.get(domain) {
+            Some(domain_cookies) => domain_cookies,
+            None => return,
+        };
+        let count = domain_cookies.values().map(NameMap::len).sum::<usize>();
+        if count <= max {
+            return;
+        }
+        // ordered by eviction preference: expired, then lowest priority, then oldest
+        let mut candidates = domain_cookies
+            .iter()
+            .flat_map(|(c_path, names)| names.iter().map(move |(c_name, c)| (c_path, c_name, c)))
+            .filter(|(c_path, c_name, _)| !(*c_path == path && *c_name == name))
+            .map(|(c_path, c_name, c)| {
+                (
+                    !c.expires_by(&now),
+                    c.priority(),
+                    c.creation_time(),
+                    c_path.clone(),
+                    c_name.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        candidates.sort();
+        for (_, _, _, c_path, c_name) in candidates.into_iter().take(count - max) {
+            debug!("evicting cookie '{c_name}' of domain '{domain}'");
+            self.remove_with_cause(
+                domain,
+                &c_path,
+                &c_name,
+                Some(request_url),
+                ChangeCause::Evicted,
+            );
+        }
+    }
+
+    /// Place `cookie` in the store under its domain, path, and name, without applying any of the
+    /// storage model rules, returning the `Cookie` it replaced, if any.
+    pub(crate) fn store_cookie(&mut self, cookie: Cookie<'static>) -> Option<Cookie<'static>> {
+        if !self.undo_log.is_active() {
+            return self.put_cookie(cookie);
+        }
+        let domain = String::from(&cookie.domain);
+        let path = String::from(&cookie.path);
+        let name = cookie.name().to_owned();
+        let old = self.put_cookie(cookie);
+        self.undo_log.record(&domain, &path, &name, old.clone());
+        old
+    }
+
+    /// As [`CookieStore::store_cookie`], without recording the change
+    fn put_cookie(&mut self, cookie: Cookie<'static>) -> Option<Cookie<'static>> {
+        let ncs = self
+            .cookies
+            .entry(String::from(&cookie.domain))
+            .or_default()
+            .entry(String::from(&cookie.path))
+            .or_default();
+        // the replaced cookie may share its index entry with `cookie`, so is removed first
+        if let Some(old) = ncs.get(cookie.name()) {
+            self.expiry_index.remove(old);
+        }
+        self.expiry_index.insert(&cookie);
+        ncs.insert(cookie.name().to_owned(), cookie)
+    }
+
     /// Clear the contents of the store
     pub fn clear(&mut self) {
-        self.cookies.clear()
+        let cookies = std::mem::take(&mut self.cookies);
+        self.expiry_index.clear();
+        if !self.observers.is_empty() {
+            for cookie in cookies
+                .values()
+                .flat_map(|dcs| dcs.values())
+                .flat_map(|pcs| pcs.values())
+            {
+                self.observers.notify(StoreChange {
+                    cause: ChangeCause::Cleared,
+                    request_url: None,
+                    old: Some(cookie),
+                    new: None,
+                });
+            }
+        }
+        if self.undo_log.is_active() {
+            for (domain, dcs) in cookies {
+                for (path, pcs) in dcs {
+                    for (name, cookie) in pcs {
+                        self.undo_log.record(&domain, &path, &name, Some(cookie));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Begin a [`Transaction`], within which changes to the store may be committed or rolled
+    /// back atomically
+    pub fn transaction(&mut self) -> Transaction<'_> {
+        Transaction::new(self)
+    }
+
+    /// Undo the changes recorded since `savepoint`, most recent first, and close the savepoint
+    pub(crate) fn rollback_to(&mut self, savepoint: usize) {
+        while let Some(entry) = self.undo_log.pop_to(savepoint) {
+            let current = match entry.prev {
+                Some(prev) => self.put_cookie(prev),
+                None => self.take_cookie(&entry.domain, &entry.path, &entry.name),
+            };
+            if !self.observers.is_empty() {
+                self.observers.notify(StoreChange {
+                    cause: ChangeCause::RolledBack,
+                    request_url: None,
+                    old: current.as_ref(),
+                    new: self.get_any(&entry.domain, &entry.path, &entry.name),
+                });
+            }
+        }
+        self.undo_log.end();
     }
 
     /// An iterator visiting all the __unexpired__ cookies in the store
     pub fn iter_unexpired<'a>(&'a self) -> impl Iterator<Item = &'a Cookie<'static>> + 'a {
-        self.cookies
-            .values()
-            .flat_map(|dcs| dcs.values())
-            .flat_map(|pcs| pcs.values())
-            .filter(|c| !c.is_expired())
+        self.iter_unexpired_at(self.now())
+    }
+
+    /// An iterator visiting all the cookies in the store which are __unexpired__ as of `utc_tm`
+    pub fn iter_unexpired_at<'a>(
+        &'a self,
+        utc_tm: OffsetDateTime,
+    ) -> impl Iterator<Item = &'a Cookie<'static>> + 'a {
+        self.iter_any().filter(move |c| !c.expires_by(&utc_tm))
     }
 
     /// An iterator visiting all (including __expired__) cookies in the store
@@ -359,13 +1027,306 @@ impl CookieStore {
             .flat_map(|pcs| pcs.values())
     }
 
+    /// An iterator visiting the cookies in the store selected by `query`
+    pub fn select<'a>(
+        &'a self,
+        query: &'a CookieQuery,
+    ) -> impl Iterator<Item = &'a Cookie<'static>> + 'a {
+        let now = self.now();
+        self.iter_any().filter(move |c| query.matches_at(c, now))
+    }
+
+    /// Removes the cookies in the store selected by `query`, returning them
+    pub fn remove_selected(&mut self, query: &CookieQuery) -> Vec<Cookie<'static>> {
+        let now = self.now();
+        self.remove_where(|c| query.matches_at(c, now))
+    }
+
+    /// Expires the cookies in the store selected by `query`, returning the number of cookies
+    /// expired. Expired cookies remain in the store, but are no longer returned for requests.
+    pub fn expire_selected(&mut self, query: &CookieQuery) -> usize {
+        let now = self.now();
+        let keys = self.keys_where(|c| query.matches_at(c, now));
+        keys.into_iter()
+            .filter(|(domain, path, name)| {
+                self.expire_cookie(domain, path, name, None, ChangeCause::Expired)
+            })
+            .count()
+    }
+
+    /// Expire the `Cookie` under `domain`, `path`, and `name` in place, recording and notifying
+    /// the change. Returns false if there was no such `Cookie`.
+    pub(crate) fn expire_cookie(
+        &mut self,
+        domain: &str,
+        path: &str,
+        name: &str,
+        request_url: Option<&Url>,
+        cause: ChangeCause,
+    ) -> bool {
+        let old = match self.get_mut_any(domain, path, name) {
+            Some(cookie) => {
+                let old = cookie.clone();
+                cookie.expire();
+                old
+            }
+            None => return false,
+        };
+        self.expiry_index.remove(&old);
+        if let Some(new) = self
+            .cookies
+            .get(domain)
+            .and_then(|pcs| pcs.get(path))
+            .and_then(|ncs| ncs.get(name))
+        {
+            self.expiry_index.insert(new);
+        }
+        self.observers.notify(StoreChange {
+            cause,
+            request_url,
+            old: Some(&old),
+            new: self.get_any(domain, path, name),
+        });
+        if self.undo_log.is_active() {
+            self.undo_log.record(domain, path, name, Some(old));
+        }
+        true
+    }
+
+    /// Merge the cookies of `other` into the store, resolving any conflict with an __unexpired__
+    /// `Cookie` already in the store per `strategy`. __Expired__ cookies of `other` are skipped.
+    pub fn merge(&mut self, other: CookieStore, strategy: &MergeStrategy) -> MergeReport {
+        self.merge_cookies(
+            other
+                .cookies
+                .into_values()
+                .flat_map(|dcs| dcs.into_values())
+                .flat_map(|pcs| pcs.into_values()),
+            strategy,
+        )
+    }
+
+    /// As [`CookieStore::merge`], for cookies from any source, e.g. loaded via
+    /// [`serde::merge`](crate::serde::merge). The cookies are merged as-is, without applying the
+    /// storage model rules of [`CookieStore::insert`].
+    pub fn merge_cookies<I>(&mut self, cookies: I, strategy: &MergeStrategy) -> MergeReport
+    where
+        I: IntoIterator<Item = Cookie<'static>>,
+    {
+        let now = self.now();
+        let mut report = MergeReport::default();
+        for cookie in cookies {
+            if cookie.expires_by(&now) {
+                report.skipped_expired += 1;
+                continue;
+            }
+            let domain = String::from(&cookie.domain);
+            let path = String::from(&cookie.path);
+            let name = cookie.name().to_owned();
+            let resolution = self
+                .get_any(&domain, &path, &name)
+                .filter(|existing| !existing.expires_by(&now))
+                .map(|existing| strategy.resolve(existing, &cookie));
+            match resolution {
+                None => report.added += 1,
+                Some(resolution) => {
+                    report.conflicts.push(MergeConflict {
+                        domain: domain.clone(),
+                        path: path.clone(),
+                        name: name.clone(),
+                        resolution,
+                    });
+                    if resolution == MergeChoice::Existing {
+                        continue;
+                    }
+                }
+            }
+            let old = self.store_cookie(cookie);
+            if !self.observers.is_empty() {
+                self.observers.notify(StoreChange {
+                    cause: if old.is_none() {
+                        ChangeCause::Inserted
+                    } else {
+                        ChangeCause::UpdatedExisting
+                    },
+                    request_url: None,
+                    old: old.as_ref(),
+                    new: self.get_any(&domain, &path, &name),
+                });
+            }
+        }
+        report
+    }
+
+    /// The differences from this store to `other`, e.g. a later version of it; see [`StoreDiff`]
+    pub fn diff(&self, other: &CookieStore) -> StoreDiff {
+        StoreDiff::new_at(self.iter_any(), other.iter_any(), self.now())
+    }
+
+    /// A summary of the contents of the store; see [`StoreStats`]. Cookies are counted by
+    /// registrable domain only if the store has a public suffix list.
+    ///
+    /// The summary is computed on each call, by visiting every cookie in the store and
+    /// allocating an entry per distinct domain; its cost is thus proportional to the size of the
+    /// store, and it is better suited to periodic monitoring than to use on every request.
+    pub fn stats(&self) -> StoreStats {
+        let now = self.now();
+        let mut stats = StoreStats::default();
+        #[cfg(feature = "public_suffix")]
+        if self.public_suffix_list.is_some() {
+            stats.per_registrable_domain = Some(Default::default());
+        }
+        for cookie in self.iter_any() {
+            #[cfg(feature = "public_suffix")]
+            let domain = cookie.domain.as_cow().unwrap_or_default();
+            #[cfg(feature = "public_suffix")]
+            let registrable = self
+                .public_suffix_list
+                .as_ref()
+                .map(|psl| registrable_domain(&domain, psl));
+            #[cfg(not(feature = "public_suffix"))]
+            let registrable = None;
+            stats.record(cookie, &now, registrable);
+        }
+        stats.next_expiration = self.next_expiration();
+        stats
+    }
+
+    /// Removes all __expired__ cookies from the store, returning them in order of expiry
+    pub fn purge_expired(&mut self) -> Vec<Cookie<'static>> {
+        self.purge_expired_batch(usize::MAX)
+    }
+
+    /// Removes up to `max` __expired__ cookies from the store, those which expired earliest
+    /// first, returning them. Use this to purge incrementally, e.g. a few cookies per request,
+    /// rather than all at once.
+    pub fn purge_expired_batch(&mut self, max: usize) -> Vec<Cookie<'static>> {
+        let now = self.now();
+        let keys = self
+            .expiry_index
+            .range(None, Some(now))
+            .take(max)
+            .map(|(_, domain, path, name)| (domain.to_owned(), path.to_owned(), name.to_owned()))
+            .collect();
+        self.remove_keys(keys)
+    }
+
+    /// The time at which the next __unexpired__ __persistent__ `Cookie` in the store expires,
+    /// if any
+    pub fn next_expiration(&self) -> Option<OffsetDateTime> {
+        self.expiry_index
+            .range(Some(self.now()), None)
+            .next()
+            .map(|(utc_tm, _, _, _)| *utc_tm)
+    }
+
+    /// An iterator visiting the __unexpired__ cookies in the store which expire within
+    /// `duration` from now, in order of expiry, e.g. to refresh a login before its session
+    /// cookie lapses. A `duration` reaching beyond the greatest representable time selects all
+    /// __unexpired__ __persistent__ cookies; a negative `duration` selects none.
+    pub fn expiring_within(
+        &self,
+        duration: time::Duration,
+    ) -> impl Iterator<Item = &Cookie<'static>> {
+        let now = self.now();
+        let until = if duration.is_negative() {
+            Some(now)
+        } else {
+            now.checked_add(duration)
+        };
+        self.expiry_index
+            .range(Some(now), until)
+            .filter_map(move |(_, domain, path, name)| self.get_any(domain, path, name))
+    }
+
+    /// Retains only the cookies (including __expired__ cookies) for which `predicate` returns
+    /// true, returning those removed
+    pub fn retain<F>(&mut self, mut predicate: F) -> Vec<Cookie<'static>>
+    where
+        F: FnMut(&Cookie<'static>) -> bool,
+    {
+        self.remove_where(|c| !predicate(c))
+    }
+
+    /// Removes all cookies whose domain is `domain`, returning them. Cookies of subdomains of
+    /// `domain` are retained; see [`CookieStore::remove_registrable_domain`].
+    pub fn remove_domain(&mut self, domain: &str) -> Vec<Cookie<'static>> {
+        let domain = canonical_domain(domain);
+        self.remove_where(|c| c.domain.as_cow().map_or(false, |d| d == domain.as_str()))
+    }
+
+    /// Removes all cookies whose domain has the same registrable domain as `domain` (e.g. for
+    /// `www.example.co.uk`, those of `example.co.uk` and all of its subdomains), returning them.
+    /// Without a public suffix list, `domain` is taken to be the registrable domain.
+    pub fn remove_registrable_domain(&mut self, domain: &str) -> Vec<Cookie<'static>> {
+        let domain = canonical_domain(domain);
+        #[cfg(feature = "public_suffix")]
+        if let Some(ref psl) = self.public_suffix_list {
+            let registrable = registrable_domain(&domain, psl);
+            let keys = self.keys_where(|c| {
+                c.domain
+                    .as_cow()
+                    .map_or(false, |d| registrable_domain(&d, psl) == registrable)
+            });
+            return self.remove_keys(keys);
+        }
+        self.remove_where(|c| {
+            c.domain
+                .as_cow()
+                .map_or(false, |d| is_same_or_subdomain(&d, &domain))
+        })
+    }
+
+    /// Removes all __non-persistent__ cookies, i.e. those which expire at
+    /// [`CookieExpiration::SessionEnd`](crate::CookieExpiration::SessionEnd), returning them.
+    /// Call this when the session the store belongs to ends.
+    pub fn end_session(&mut self) -> Vec<Cookie<'static>> {
+        self.remove_where(|c| !c.is_persistent())
+    }
+
+    /// Removes all cookies (including __expired__ cookies) for which `predicate` returns true,
+    /// returning them
+    fn remove_where<F>(&mut self, predicate: F) -> Vec<Cookie<'static>>
+    where
+        F: FnMut(&Cookie<'static>) -> bool,
+    {
+        let keys = self.keys_where(predicate);
+        self.remove_keys(keys)
+    }
+
+    /// Removes the cookies with the given (domain, path, name) keys, returning those which were
+    /// in the store
+    fn remove_keys(&mut self, keys: Vec<(String, String, String)>) -> Vec<Cookie<'static>> {
+        keys.into_iter()
+            .filter_map(|(domain, path, name)| self.remove(&domain, &path, &name))
+            .collect()
+    }
+
+    /// The (domain, path, name) keys of the cookies (including __expired__ cookies) for which
+    /// `predicate` returns true
+    fn keys_where<F>(&self, mut predicate: F) -> Vec<(String, String, String)>
+    where
+        F: FnMut(&Cookie<'static>) -> bool,
+    {
+        self.iter_any()
+            .filter(|c| predicate(c))
+            .map(|c| {
+                (
+                    String::from(&c.domain),
+                    String::from(&c.path),
+                    c.name().to_owned(),
+                )
+            })
+            .collect()
+    }
+
     /// Serialize any __unexpired__ and __persistent__ cookies in the store with `cookie_to_string`
     /// and write them to `writer`
     pub fn save<W, E, F>(&self, writer: &mut W, cookie_to_string: F) -> StoreResult<()>
     where
         W: Write,
         F: Fn(&Cookie<'static>) -> Result<String, E>,
-        crate::Error: From<E>,
+        StoreError: From<E>,
     {
         for cookie in self.iter_unexpired().filter_map(|c| {
             if c.is_persistent() {
@@ -388,7 +1349,7 @@ impl CookieStore {
     where
         W: Write,
         F: Fn(&Cookie<'static>) -> Result<String, E>,
-        crate::Error: From<E>,
+        StoreError: From<E>,
     {
         for cookie in self.iter_any() {
             writeln!(writer, "{}", cookie_to_string(cookie)?)?;
@@ -402,7 +1363,7 @@ impl CookieStore {
     where
         R: BufRead,
         F: Fn(&str) -> Result<Cookie<'static>, E>,
-        crate::Error: From<E>,
+        StoreError: From<E>,
     {
         CookieStore::load_from(reader, cookie_from_str, false)
     }
@@ -413,7 +1374,7 @@ impl CookieStore {
     where
         R: BufRead,
         F: Fn(&str) -> Result<Cookie<'static>, E>,
-        crate::Error: From<E>,
+        StoreError: From<E>,
     {
         CookieStore::load_from(reader, cookie_from_str, true)
     }
@@ -426,13 +1387,17 @@ impl CookieStore {
     where
         R: BufRead,
         F: Fn(&str) -> Result<Cookie<'static>, E>,
-        crate::Error: From<E>,
+        StoreError: From<E>,
     {
-        let cookies = reader.lines().map(|line_result| {
-            line_result
-                .map_err(Into::into)
-                .and_then(|line| cookie_from_str(&line).map_err(crate::Error::from))
-        });
+        let cookies = reader
+            .lines()
+            .enumerate()
+            .map(|(index, line_result)| {
+                let cookie = cookie_from_str(&line_result?)
+                    .map_err(|e| StoreError::from(e).at_line(index + 1))?;
+                check_loaded(index, cookie)
+            })
+            .filter_map(Result::transpose);
         Self::from_cookies(cookies, include_expired)
     }
 
@@ -440,28 +1405,32 @@ impl CookieStore {
     /// `include_expired` is `true`, both __expired__ and __unexpired__ cookies in the incoming
     /// iterator will be included in the produced `CookieStore`; otherwise, only
     /// __unexpired__ cookies will be included, and __expired__ cookies filtered
-    /// out.
+    /// out. Expiry is determined per the system clock; see [`CookieStore::from_cookies_at`].
     pub fn from_cookies<I, E>(iter: I, include_expired: bool) -> Result<Self, E>
     where
         I: IntoIterator<Item = Result<Cookie<'static>, E>>,
     {
-        let mut cookies = Map::new();
+        Self::from_cookies_at(iter, include_expired, OffsetDateTime::now_utc())
+    }
+
+    /// As [`CookieStore::from_cookies`], with expiry determined as of `utc_tm`; e.g. the current
+    /// time of the [`Clock`] the store will be given via [`CookieStore::with_clock`]
+    pub fn from_cookies_at<I, E>(
+        iter: I,
+        include_expired: bool,
+        utc_tm: OffsetDateTime,
+    ) -> Result<Self, E>
+    where
+        I: IntoIterator<Item = Result<Cookie<'static>, E>>,
+    {
+        let mut store = Self::new();
         for cookie in iter {
             let cookie = cookie?;
-            if include_expired || !cookie.is_expired() {
-                cookies
-                    .entry(String::from(&cookie.domain))
-                    .or_insert_with(Map::new)
-                    .entry(String::from(&cookie.path))
-                    .or_insert_with(Map::new)
-                    .insert(cookie.name().to_owned(), cookie);
+            if include_expired || !cookie.expires_by(&utc_tm) {
+                store.store_cookie(cookie);
             }
         }
-        Ok(Self {
-            cookies,
-            #[cfg(feature = "public_suffix")]
-            public_suffix_list: None,
-        })
+        Ok(store)
     }
 
     pub fn new() -> Self {
@@ -469,14 +1438,21 @@ impl CookieStore {
             cookies: DomainMap::new(),
             #[cfg(feature = "public_suffix")]
             public_suffix_list: None,
+            policy: None,
+            third_party_exceptions: None,
+            clock: None,
+            max_cookies_per_domain: None,
+            observers: Observers::default(),
+            undo_log: UndoLog::default(),
+            expiry_index: ExpiryIndex::default(),
         }
     }
 
     #[cfg(feature = "public_suffix")]
     pub fn new_with_public_suffix(public_suffix_list: Option<publicsuffix::List>) -> Self {
         Self {
-            cookies: DomainMap::new(),
-            public_suffix_list,
+            public_suffix_list: public_suffix_list.map(Arc::new),
+            ..Self::new()
         }
     }
 }
@@ -600,7 +1576,7 @@ mod tests {
     use super::CookieStore;
     use super::{InsertResult, StoreAction};
     use crate::cookie::Cookie;
-    use crate::CookieError;
+    use crate::CookieErrorKind;
     use ::cookie::Cookie as RawCookie;
     use time::OffsetDateTime;
 
@@ -623,22 +1599,31 @@ mod tests {
     }
     macro_rules! domain_mismatch {
         ($e: expr) => {
-            assert_eq!(Err(CookieError::DomainMismatch), $e)
+            assert_eq!(
+                Err(CookieErrorKind::DomainMismatch),
+                $e.map_err(|e| e.kind())
+            )
         };
     }
     macro_rules! non_http_scheme {
         ($e: expr) => {
-            assert_eq!(Err(CookieError::NonHttpScheme), $e)
+            assert_eq!(
+                Err(CookieErrorKind::NonHttpScheme),
+                $e.map_err(|e| e.kind())
+            )
         };
     }
     macro_rules! non_rel_scheme {
         ($e: expr) => {
-            assert_eq!(Err(CookieError::NonRelativeScheme), $e)
+            assert_eq!(
+                Err(CookieErrorKind::NonRelativeScheme),
+                $e.map_err(|e| e.kind())
+            )
         };
     }
     macro_rules! expired_err {
         ($e: expr) => {
-            assert_eq!(Err(CookieError::Expired), $e)
+            assert_eq!(Err(CookieErrorKind::Expired), $e.map_err(|e| e.kind()))
         };
     }
     macro_rules! values_are {
@@ -838,6 +1823,37 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn store_response_set_cookie_headers() {
+        let url = test_utils::url("http://example.com/foo/bar");
+        let set_cookies = [
+            "persistent=1; Expires=9 June 2100 10:18:14; Priority=High",
+            "invalid",
+            "other=1; Domain=other.com",
+        ];
+        let mut store = CookieStore::default();
+        store.store_response_set_cookie_headers(set_cookies, &url);
+        assert_eq!(1, store.iter_any().count());
+        let cookie = store.get("example.com", "/foo", "persistent").unwrap();
+        assert_eq!(
+            crate::CookieExpiration::AtUtc(time::macros::datetime!(2100-06-09 10:18:14 UTC)),
+            cookie.expires
+        );
+        assert_eq!(Some(set_cookies[0]), cookie.set_cookie_header());
+        assert_eq!(crate::CookiePriority::High, cookie.priority());
+
+        // the fallback is unavailable to an already-parsed `RawCookie`
+        let mut store = CookieStore::default();
+        store.store_response_cookies(
+            std::iter::once(RawCookie::parse(set_cookies[0]).unwrap()),
+            &url,
+        );
+        let cookie = store.get("example.com", "/foo", "persistent").unwrap();
+        assert_eq!(crate::CookieExpiration::SessionEnd, cookie.expires);
+        // as is the Priority attribute
+        assert_eq!(crate::CookiePriority::Medium, cookie.priority());
+    }
+
     #[test]
     fn domains() {
         let mut store = CookieStore::default();
@@ -1242,12 +2258,528 @@ mod tests {
         matches_are(&store, "http://bus.example.com", vec![]);
     }
 
+    #[test]
+    fn third_party_blocking() {
+        let site = test_utils::url("https://www.example.com/");
+        let exception = test_utils::url("https://partner.org/");
+        let mut store = CookieStore::default().with_third_party_blocking(["partner.org"]);
+        let parse = |cookie: &str, url: &str| {
+            Cookie::parse(cookie.to_owned(), &test_utils::url(url)).unwrap()
+        };
+
+        // first-party, including a parent domain of the top-level site
+        inserted!(store.insert_for_site(
+            parse("cookie1=1", "https://www.example.com/"),
+            &test_utils::url("https://www.example.com/"),
+            &site,
+        ));
+        inserted!(store.insert_for_site(
+            parse("cookie2=2; Domain=example.com", "https://api.example.com/"),
+            &test_utils::url("https://api.example.com/"),
+            &site,
+        ));
+        // third-party
+        assert_eq!(
+            Err(CookieErrorKind::ThirdParty),
+            store
+                .insert_for_site(
+                    parse("cookie3=3", "https://tracker.com/"),
+                    &test_utils::url("https://tracker.com/"),
+                    &site,
+                )
+                .map_err(|e| e.kind())
+        );
+        // allowed in the context of an exception site
+        inserted!(store.insert_for_site(
+            parse("cookie3=3", "https://tracker.com/"),
+            &test_utils::url("https://tracker.com/"),
+            &exception,
+        ));
+        // blocking applies only to the site-aware methods
+        inserted!(add_cookie(
+            &mut store,
+            "cookie4=4",
+            "https://tracker.com/",
+            None,
+            None
+        ));
+
+        let url = test_utils::url("https://tracker.com/");
+        assert_eq!(2, store.matches(&url).len());
+        assert_eq!(0, store.matches_for_site(&url, &site).len());
+        assert_eq!(2, store.matches_for_site(&url, &exception).len());
+        assert_eq!(2, store.matches_for_site(&url, &url).len());
+        let url = test_utils::url("https://api.example.com/");
+        assert_eq!(1, store.matches_for_site(&url, &site).len());
+    }
+
+    #[test]
+    fn third_party_blocking_sibling_subdomain() {
+        let site = test_utils::url("https://a.example.com/");
+        let url = test_utils::url("https://b.example.com/");
+        let mut store = CookieStore::default().with_third_party_blocking(Vec::<String>::new());
+        inserted!(store.insert_for_site(Cookie::parse("cookie1=1", &url).unwrap(), &url, &site,));
+        assert_eq!(1, store.matches_for_site(&url, &site).len());
+        assert_eq!(
+            Err(CookieErrorKind::ThirdParty),
+            store
+                .insert_for_site(
+                    Cookie::parse("cookie2=2", &test_utils::url("https://b.example.org/")).unwrap(),
+                    &test_utils::url("https://b.example.org/"),
+                    &site,
+                )
+                .map_err(|e| e.kind())
+        );
+    }
+
+    #[cfg(feature = "public_suffix")]
+    #[test]
+    fn third_party_blocking_public_suffix() {
+        let psl: publicsuffix::List = "// ===BEGIN ICANN DOMAINS===\ncom\nuk\nco.uk\n"
+            .parse()
+            .unwrap();
+        let mut store = CookieStore::default()
+            .with_suffix_list(psl)
+            .with_third_party_blocking(Vec::<String>::new());
+        let site = test_utils::url("https://www.example.co.uk/");
+        for (url, exp) in [
+            ("https://example.co.uk/", Ok(StoreAction::Inserted)),
+            ("https://shop.example.co.uk/", Ok(StoreAction::Inserted)),
+            ("https://other.co.uk/", Err(CookieErrorKind::ThirdParty)),
+        ] {
+            let url = test_utils::url(url);
+            let cookie = Cookie::parse("cookie1=1", &url).unwrap();
+            assert_eq!(
+                exp,
+                store
+                    .insert_for_site(cookie, &url, &site)
+                    .map_err(|e| e.kind()),
+                "{url}"
+            );
+        }
+        let url = test_utils::url("https://shop.example.co.uk/");
+        assert_eq!(1, store.matches_for_site(&url, &site).len());
+        let other = test_utils::url("https://other.co.uk/");
+        assert_eq!(0, store.matches_for_site(&url, &other).len());
+    }
+
+    #[test]
+    fn maintenance() {
+        let mut store = test_utils::store(&[
+            ("a=1; Max-Age=3600", "http://example.com/"),
+            ("b=2", "http://example.com/"),
+            ("c=3; Max-Age=3600", "http://www.example.com/"),
+            ("d=4", "http://www.example.com/foo/"),
+            ("e=5; Max-Age=3600", "http://example.org/"),
+            ("f=6; Max-Age=3600", "http://other.example.org/"),
+        ]);
+        assert!(store.expire_cookie("example.org", "/", "e", None, super::ChangeCause::Expired));
+
+        assert_eq!(vec!["e=5"], test_utils::values(store.purge_expired()));
+        assert!(store.purge_expired().is_empty());
+        assert_eq!(vec!["b=2", "d=4"], test_utils::values(store.end_session()));
+        assert_eq!(
+            vec!["a=1"],
+            test_utils::values(store.remove_domain(".EXAMPLE.com"))
+        );
+        assert_eq!(
+            vec!["f=6"],
+            test_utils::values(store.retain(|c| c.name() != "f"))
+        );
+        assert_eq!(1, store.iter_any().count());
+        assert!(store.remove_registrable_domain("example.org").is_empty());
+        assert_eq!(
+            vec!["c=3"],
+            test_utils::values(store.remove_registrable_domain("example.com"))
+        );
+        assert_eq!(0, store.iter_any().count());
+    }
+
+    #[test]
+    fn iter_matches() {
+        let store = make_match_store();
+        for url in [
+            "http://example.com/foo/bar",
+            "https://example.com/sec/foo",
+            "http://bar.example.org/foo/bar",
+            "http://unknowndomain.org/foo/bar",
+        ] {
+            let url = test_utils::url(url);
+            let mut exp = store.matches(&url);
+            let mut lazy = store.iter_matches(&url).collect::<Vec<_>>();
+            exp.sort_by_key(|c| c.name());
+            lazy.sort_by_key(|c| c.name());
+            assert_eq!(exp, lazy, "{url}");
+        }
+    }
+
+    #[test]
+    fn get_request_values() {
+        let mut store = CookieStore::default();
+        store
+            .parse("cookie1=value1", &test_utils::url("https://example.com/"))
+            .unwrap();
+        // the values do not borrow the url
+        let values = store.get_request_values(&test_utils::url("https://example.com/foo"));
+        assert_eq!(vec![("cookie1", "value1")], values.collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn write_cookie_header() {
+        let mut store = CookieStore::default();
+        let url = test_utils::url("https://example.com/foo");
+        let mut header = String::new();
+        assert_eq!(Ok(0), store.write_cookie_header(&url, &mut header));
+        assert!(header.is_empty());
+
+        store.parse("cookie1=value1", &url).unwrap();
+        assert_eq!(Ok(1), store.write_cookie_header(&url, &mut header));
+        assert_eq!("cookie1=value1", header);
+
+        store.parse("cookie2=value2; Secure", &url).unwrap();
+        header.clear();
+        assert_eq!(Ok(2), store.write_cookie_header(&url, &mut header));
+        let mut pairs = header.split("; ").collect::<Vec<_>>();
+        pairs.sort_unstable();
+        assert_eq!(vec!["cookie1=value1", "cookie2=value2"], pairs);
+
+        header.clear();
+        let url = test_utils::url("http://example.com/foo");
+        assert_eq!(Ok(1), store.write_cookie_header(&url, &mut header));
+        assert_eq!("cookie1=value1", header);
+    }
+
+    #[test]
+    fn write_cookie_header_within() {
+        let mut store = CookieStore::default();
+        let url = test_utils::url("https://example.com/a/b/c");
+        store.parse("high=1; Priority=High; Path=/a", &url).unwrap();
+        store
+            .parse("low=2; Priority=Low; Path=/a/b/c", &url)
+            .unwrap();
+        store.parse("medium=3; Path=/a/b", &url).unwrap();
+        let header = |max_len| {
+            let mut header = String::new();
+            let written = store
+                .write_cookie_header_within(&url, max_len, &mut header)
+                .unwrap();
+            assert!(header.len() <= max_len);
+            let mut pairs = header
+                .split("; ")
+                .filter(|p| !p.is_empty())
+                .collect::<Vec<_>>();
+            assert_eq!(written, pairs.len());
+            pairs.sort_unstable();
+            pairs.join("; ")
+        };
+        assert_eq!("high=1; low=2; medium=3", header(100));
+        assert_eq!("high=1; low=2; medium=3", header(23));
+        assert_eq!("high=1; medium=3", header(22));
+        // a lower priority cookie is admitted if a higher priority one does not fit
+        assert_eq!("high=1; low=2", header(15));
+        assert_eq!("high=1", header(12));
+        assert_eq!("low=2", header(5));
+        assert_eq!("", header(4));
+    }
+
+    #[test]
+    fn max_cookies_per_domain() {
+        use crate::{ChangeCause, ManualClock};
+        use std::sync::{Arc, Mutex};
+        use time::Duration;
+
+        let clock = ManualClock::new(OffsetDateTime::UNIX_EPOCH + Duration::days(365));
+        let mut store = CookieStore::default()
+            .with_clock(clock.clone())
+            .with_max_cookies_per_domain(3);
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let observer_evicted = evicted.clone();
+        store.add_observer(move |change| {
+            if change.cause == ChangeCause::Evicted {
+                let name = change.old.unwrap().name().to_owned();
+                observer_evicted.lock().unwrap().push(name);
+            }
+        });
+        let url = test_utils::url("http://example.com/");
+        let parse = |store: &mut CookieStore, cookie_str| {
+            clock.advance(Duration::seconds(1));
+            store.parse(cookie_str, &url).unwrap()
+        };
+        parse(&mut store, "low1=1; Priority=Low");
+        parse(&mut store, "medium=1");
+        parse(&mut store, "low2=1; Priority=Low");
+        parse(&mut store, "high=1; Priority=High");
+        // the oldest of the lowest priority
+        assert_eq!(vec!["low1"], *evicted.lock().unwrap());
+        parse(&mut store, "expiring=1; Max-Age=60");
+        assert_eq!(vec!["low1", "low2"], *evicted.lock().unwrap());
+        // updating a cookie does not evict
+        parse(&mut store, "medium=2");
+        clock.advance(Duration::minutes(2));
+        // expired cookies are evicted first, even of higher priority
+        parse(&mut store, "new=1; Priority=Low");
+        assert_eq!(vec!["low1", "low2", "expiring"], *evicted.lock().unwrap());
+        let mut names = store.iter_any().map(|c| c.name()).collect::<Vec<_>>();
+        names.sort_unstable();
+        assert_eq!(vec!["high", "medium", "new"], names);
+
+        // the limit applies to each domain separately
+        let url = test_utils::url("http://www.example.com/");
+        for name in ["a", "b", "c"] {
+            store.parse(&format!("{name}=1"), &url).unwrap();
+        }
+        assert_eq!(6, store.iter_any().count());
+        assert_eq!(3, evicted.lock().unwrap().len());
+    }
+
+    #[test]
+    fn manual_clock() {
+        use crate::{CookieExpiration, ManualClock};
+        use time::Duration;
+
+        let start = OffsetDateTime::UNIX_EPOCH + Duration::days(365);
+        let clock = ManualClock::new(start);
+        let mut store = CookieStore::default().with_clock(clock.clone());
+        let url = test_utils::url("http://example.com/");
+        assert_eq!(
+            Ok(StoreAction::Inserted),
+            store.parse("cookie1=value1; Max-Age=3600", &url)
+        );
+        assert_eq!(
+            Ok(StoreAction::Inserted),
+            store.parse("cookie2=value2; Max-Age=7200", &url)
+        );
+        assert_eq!(
+            CookieExpiration::AtUtc(start + Duration::hours(1)),
+            store.get("example.com", "/", "cookie1").unwrap().expires
+        );
+        assert_eq!(2, store.matches(&url).len());
+
+        clock.advance(Duration::hours(1));
+        assert_eq!(1, store.matches(&url).len());
+        assert_eq!(1, store.iter_unexpired().count());
+        assert!(store.get("example.com", "/", "cookie1").is_none());
+        assert_eq!(2, store.matches_at(&url, start).len());
+        assert_eq!(2, store.iter_unexpired_at(start).count());
+
+        // an expired entry is not updated by an expired cookie, but replaced by an unexpired one
+        let cookie = Cookie::parse_at("cookie1=value3; Max-Age=60", &url, start).unwrap();
+        assert_eq!(
+            Err(CookieErrorKind::Expired),
+            store.insert(cookie.clone(), &url).map_err(|e| e.kind())
+        );
+        assert_eq!(
+            Ok(StoreAction::UpdatedExisting),
+            store.insert_at(cookie, &url, start)
+        );
+
+        clock.set(start + Duration::hours(2));
+        assert_eq!(2, store.purge_expired().len());
+        assert_eq!(0, store.iter_any().count());
+    }
+
+    #[test]
+    fn manual_clock_outside_store() {
+        use crate::{Clock, CookieQuery, ManualClock};
+        use time::Duration;
+
+        let start = OffsetDateTime::UNIX_EPOCH + Duration::days(365);
+        let clock = ManualClock::new(start);
+        let url = test_utils::url("http://example.com/");
+        let cookies = [
+            "cookie1=value1; Max-Age=3600",
+            "cookie2=value2; Max-Age=7200",
+        ]
+        .iter()
+        .map(|c| Cookie::parse_at(*c, &url, start).map(Cookie::into_owned))
+        .collect::<Vec<_>>();
+        // per the system clock, both cookies expired long ago
+        assert_eq!(
+            0,
+            CookieStore::from_cookies(cookies.clone(), false)
+                .unwrap()
+                .iter_any()
+                .count()
+        );
+        let mut store = CookieStore::from_cookies_at(cookies, false, clock.now_utc())
+            .unwrap()
+            .with_clock(clock.clone());
+        assert_eq!(start, store.now());
+        assert_eq!(2, store.iter_any().count());
+
+        clock.advance(Duration::minutes(90));
+        let now = store.now();
+        let cookie1 = store.get_any("example.com", "/", "cookie1").unwrap();
+        assert!(cookie1.expires_by(&now));
+        assert!(cookie1.expires.expires_by(&now));
+        let query = CookieQuery::new().domain("example.com");
+        assert!(!query.matches_at(cookie1, now));
+        assert!(query.matches_at(store.get_any("example.com", "/", "cookie2").unwrap(), now));
+        assert_eq!(1, store.select(&query).count());
+        assert!(store.get_mut("example.com", "/", "cookie1").is_none());
+        assert!(store.get_mut("example.com", "/", "cookie2").is_some());
+    }
+
+    #[cfg(feature = "public_suffix")]
+    #[test]
+    fn remove_registrable_domain_public_suffix() {
+        let psl: publicsuffix::List = "// ===BEGIN ICANN DOMAINS===\ncom\nuk\nco.uk\n"
+            .parse()
+            .unwrap();
+        let mut store = CookieStore::default().with_suffix_list(psl);
+        for url in [
+            "https://example.co.uk/",
+            "https://shop.example.co.uk/",
+            "https://other.co.uk/",
+        ] {
+            store.parse("cookie1=1", &test_utils::url(url)).unwrap();
+        }
+        assert_eq!(
+            2,
+            store.remove_registrable_domain("www.example.co.uk").len()
+        );
+        assert_eq!(1, store.iter_any().count());
+        assert!(store.contains_any("other.co.uk", "/", "cookie1"));
+    }
+
+    /// Asserts that the expiry index holds exactly the persistent cookies of the store
+    fn check_expiry_index(store: &CookieStore) {
+        let mut expected = store
+            .iter_any()
+            .filter_map(|c| match c.expires {
+                crate::CookieExpiration::AtUtc(utc_tm) => Some((
+                    utc_tm,
+                    String::from(&c.domain),
+                    String::from(&c.path),
+                    c.name().to_owned(),
+                )),
+                crate::CookieExpiration::SessionEnd => None,
+            })
+            .collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(expected, store.expiry_index.entries());
+    }
+
+    #[test]
+    fn expiry_index() {
+        use crate::{CookieQuery, ManualClock, MergeStrategy};
+        use time::Duration;
+
+        let start = OffsetDateTime::UNIX_EPOCH + Duration::days(365);
+        let clock = ManualClock::new(start);
+        let mut store = CookieStore::default().with_clock(clock.clone());
+        let url = test_utils::url("http://example.com/");
+        for cookie in [
+            "auth=1; Max-Age=600",
+            "pref=2; Max-Age=86400",
+            "session=3",
+            "short=4; Max-Age=60",
+            "gone=5; Max-Age=120",
+        ] {
+            store.parse(cookie, &url).unwrap();
+        }
+        check_expiry_index(&store);
+        assert_eq!(Some(start + Duration::minutes(1)), store.next_expiration());
+
+        // updates, expiry in place, removals, and rollbacks are reflected in the index
+        store.parse("auth=1; Max-Age=900", &url).unwrap();
+        store.parse("gone=5; Max-Age=0", &url).unwrap();
+        store.remove("example.com", "/", "short");
+        check_expiry_index(&store);
+        {
+            let mut tx = store.transaction();
+            tx.parse("pref=2; Max-Age=30", &url).unwrap();
+            tx.expire_selected(&CookieQuery::new().name("auth"));
+            tx.clear();
+        }
+        check_expiry_index(&store);
+        let mut other = CookieStore::default().with_clock(clock.clone());
+        other.parse("merged=6; Max-Age=300", &url).unwrap();
+        store.merge(other, &MergeStrategy::KeepExisting);
+        check_expiry_index(&store);
+
+        assert_eq!(Some(start + Duration::minutes(5)), store.next_expiration());
+        let expiring = |store: &CookieStore, mins| {
+            store
+                .expiring_within(Duration::minutes(mins))
+                .map(|c| c.name().to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec!["merged"], expiring(&store, 5));
+        assert_eq!(vec!["merged", "auth"], expiring(&store, 15));
+        assert_eq!(vec!["merged", "auth", "pref"], expiring(&store, 60 * 24));
+        assert_eq!(
+            vec!["merged", "auth", "pref"],
+            store
+                .expiring_within(Duration::MAX)
+                .map(|c| c.name())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(0, store.expiring_within(Duration::seconds(-10)).count());
+        assert_eq!(0, store.expiring_within(Duration::MIN).count());
+
+        clock.advance(Duration::minutes(20));
+        assert_eq!(vec!["pref"], expiring(&store, 60 * 24));
+        assert_eq!(Some(start + Duration::days(1)), store.next_expiration());
+        let purged = store.purge_expired_batch(2);
+        assert_eq!(vec!["gone=5", "merged=6"], test_utils::values(purged));
+        check_expiry_index(&store);
+        assert_eq!(vec!["auth=1"], test_utils::values(store.purge_expired()));
+        assert!(store.purge_expired().is_empty());
+        check_expiry_index(&store);
+        assert_eq!(2, store.iter_any().count());
+
+        clock.advance(Duration::days(1));
+        assert_eq!(None, store.next_expiration());
+        assert_eq!(vec!["pref=2"], test_utils::values(store.purge_expired()));
+        check_expiry_index(&store);
+    }
+
+    #[test]
+    fn expiry_index_same_expiry() {
+        use crate::{MergeChoice, MergeStrategy};
+
+        let url = test_utils::url("http://example.com/");
+        let expires = "Expires=Wed, 21 Oct 2099 07:28:00 GMT";
+        let expected = Some(time::macros::datetime!(2099-10-21 07:28:00 UTC));
+        let mut store = CookieStore::default();
+        store.parse(&format!("a=1; {expires}"), &url).unwrap();
+
+        // an update keeping the same Expires
+        assert_eq!(
+            Ok(StoreAction::UpdatedExisting),
+            store.parse(&format!("a=2; {expires}"), &url)
+        );
+        check_expiry_index(&store);
+        assert_eq!(expected, store.next_expiration());
+
+        // a rollback restoring a predecessor with the same expiry
+        {
+            let mut tx = store.transaction();
+            tx.parse(&format!("a=3; {expires}"), &url).unwrap();
+        }
+        assert_eq!("2", store.get("example.com", "/", "a").unwrap().value());
+        check_expiry_index(&store);
+        assert_eq!(expected, store.next_expiration());
+
+        // a merge replacing the existing cookie with one of the same expiry
+        let mut other = CookieStore::default();
+        other.parse(&format!("a=4; {expires}"), &url).unwrap();
+        store.merge(
+            other,
+            &MergeStrategy::Custom(Box::new(|_, _| MergeChoice::Incoming)),
+        );
+        assert_eq!("4", store.get("example.com", "/", "a").unwrap().value());
+        check_expiry_index(&store);
+        assert_eq!(expected, store.next_expiration());
+    }
+
     #[cfg(feature = "serde_json")]
     #[allow(deprecated)]
     mod serde_json_tests {
         use super::{add_cookie, make_match_store, CookieStore, StoreAction};
         use crate::cookie::Cookie;
-        use crate::CookieError;
+        use crate::CookieErrorKind;
 
         use crate::utils::test as test_utils;
 
@@ -0,0 +1,85 @@
+use std::collections::BTreeSet;
+use std::ops::Bound;
+
+use time::{Duration, OffsetDateTime};
+
+use crate::cookie::Cookie;
+use crate::cookie_expiration::CookieExpiration;
+
+/// An entry of the index: the expiry time, domain, path, and name of a `Cookie`
+type Entry = (OffsetDateTime, String, String, String);
+
+/// The (domain, path, name) keys of the __persistent__ cookies in a `CookieStore` (including
+/// those __expired__), ordered by expiry time
+#[derive(Debug, Default, Clone)]
+pub(crate) struct ExpiryIndex(BTreeSet<Entry>);
+
+fn entry(cookie: &Cookie<'_>) -> Option<Entry> {
+    match cookie.expires {
+        CookieExpiration::AtUtc(utc_tm) => Some((
+            utc_tm,
+            String::from(&cookie.domain),
+            String::from(&cookie.path),
+            cookie.name().to_owned(),
+        )),
+        CookieExpiration::SessionEnd => None,
+    }
+}
+
+/// The least entry expiring after `utc_tm`; `None` if no time is representable after `utc_tm`
+fn first_after(utc_tm: OffsetDateTime) -> Option<Entry> {
+    utc_tm
+        .checked_add(Duration::nanoseconds(1))
+        .map(|utc_tm| (utc_tm, String::new(), String::new(), String::new()))
+}
+
+impl ExpiryIndex {
+    pub(crate) fn insert(&mut self, cookie: &Cookie<'_>) {
+        if let Some(entry) = entry(cookie) {
+            self.0.insert(entry);
+        }
+    }
+
+    pub(crate) fn remove(&mut self, cookie: &Cookie<'_>) {
+        if let Some(entry) = entry(cookie) {
+            self.0.remove(&entry);
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// The entries expiring after `after`, up to and including `until`, in order of expiry; none
+    /// if `until` precedes `after`
+    pub(crate) fn range(
+        &self,
+        after: Option<OffsetDateTime>,
+        until: Option<OffsetDateTime>,
+    ) -> impl Iterator<Item = (&OffsetDateTime, &str, &str, &str)> {
+        // an empty range rather than one ending before it starts, on which `BTreeSet::range`
+        // panics
+        let until = match (after, until) {
+            (Some(after), Some(until)) if until < after => Some(after),
+            _ => until,
+        };
+        let start = after.map(first_after);
+        // nothing can expire after the greatest representable time
+        let is_empty = matches!(start, Some(None));
+        let start = start.flatten().map_or(Bound::Unbounded, Bound::Included);
+        let end = until
+            .and_then(first_after)
+            .map_or(Bound::Unbounded, Bound::Excluded);
+        self.0
+            .range((start, end))
+            .take(if is_empty { 0 } else { usize::MAX })
+            .map(|(utc_tm, domain, path, name)| {
+                (utc_tm, domain.as_str(), path.as_str(), name.as_str())
+            })
+    }
+
+    #[cfg(test)]
+    pub(crate) fn entries(&self) -> Vec<Entry> {
+        self.0.iter().cloned().collect()
+    }
+}
@@ -7,24 +7,64 @@
 //! Please refer to the [reqwest_cookie_store](https://crates.io/crates/reqwest_cookie_store) for
 //! an example of using this library along with [reqwest](https://crates.io/crates/reqwest).
 //!
+//! ## Set-Cookie headers and `RawCookie`s
+//! Cookies may be stored from the text of their Set-Cookie headers (e.g. via [`Cookie::parse`] or
+//! [`CookieStore::store_response_set_cookie_headers`]), or from already-parsed [`RawCookie`]s
+//! (e.g. via [`CookieStore::store_response_cookies`]). Only the former retain the header text,
+//! its Priority and extension attributes, and fall back to the lenient date parsing of [RFC6265 Section
+//! 5.1.1](https://datatracker.ietf.org/doc/html/rfc6265#section-5.1.1) for an Expires attribute
+//! in a non-standard format; a `RawCookie` with such an attribute is stored as a session cookie.
+//!
 //! ## Feature flags
 #![doc = document_features::document_features!()]
 
-pub use ::cookie::{Cookie as RawCookie, ParseError as RawCookieParseError};
+pub use ::cookie::{Cookie as RawCookie, ParseError as RawCookieParseError, SameSite};
 
+mod clock;
+pub use crate::clock::{Clock, ManualClock, SystemClock};
 mod cookie;
-pub use crate::cookie::Error as CookieError;
+mod cookie_builder;
+mod cookie_date;
 pub use crate::cookie::{Cookie, CookieResult};
+pub use crate::cookie::{Error as CookieError, ErrorKind as CookieErrorKind};
+pub use crate::cookie_builder::CookieBuilder;
 mod cookie_domain;
 pub use crate::cookie_domain::CookieDomain;
 mod cookie_expiration;
 pub use crate::cookie_expiration::CookieExpiration;
+mod cookie_jars;
+pub use crate::cookie_jars::CookieJars;
 mod cookie_path;
 pub use crate::cookie_path::CookiePath;
+mod cookie_policy;
+pub use crate::cookie_policy::{
+    AllowedDomains, And, BlockedDomains, CookiePolicy, DefaultCookiePolicy, Or,
+};
+mod cookie_priority;
+pub use crate::cookie_priority::CookiePriority;
+mod cookie_query;
+pub use crate::cookie_query::CookieQuery;
 mod cookie_store;
+mod expiry_index;
 pub use crate::cookie_store::{CookieStore, StoreAction};
 #[cfg(feature = "serde")]
 pub mod serde;
+mod store_diff;
+mod store_error;
+pub use crate::store_diff::{DiffEntry, DiffKind, FieldChange, StoreDiff};
+pub use crate::store_error::StoreError;
+mod store_merge;
+pub use crate::store_merge::{MergeChoice, MergeConflict, MergeReport, MergeStrategy};
+mod store_observer;
+pub use crate::store_observer::{ChangeCause, ObserverId, StoreChange};
+mod store_overlay;
+pub use crate::store_overlay::OverlayCookieStore;
+mod store_report;
+pub use crate::store_report::{SetCookieOutcome, SetCookieReport};
+mod store_stats;
+pub use crate::store_stats::{SameSiteCounts, StoreStats};
+mod store_transaction;
+pub use crate::store_transaction::Transaction;
 mod utils;
 
 #[derive(Debug)]
@@ -57,31 +97,64 @@ pub(crate) mod rfc3339_fmt {
     where
         S: serde::Serializer,
     {
-        use serde::ser::Error;
+        serializer.serialize_str(&format::<S::Error>(t)?)
+    }
+
+    fn format<E: serde::ser::Error>(t: &time::OffsetDateTime) -> Result<String, E> {
         // An explicit format string is used here, instead of time::format_description::well_known::Rfc3339, to explicitly
         // utilize the 'Z' terminator instead of +00:00 format for Zulu time.
-        let s = t.format(&RFC3339_FORMAT).map_err(|e| {
+        t.format(&RFC3339_FORMAT).map_err(|e| {
             println!("{e}");
-            S::Error::custom(format!(
+            E::custom(format!(
                 "Could not parse datetime '{t}' as RFC3339 UTC format: {e}"
             ))
-        })?;
-        serializer.serialize_str(&s)
+        })
     }
 
     pub(super) fn deserialize<'de, D>(t: D) -> Result<time::OffsetDateTime, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        use serde::{de::Error, Deserialize};
+        use serde::Deserialize;
 
         let s = String::deserialize(t)?;
-        time::OffsetDateTime::parse(&s, &time::format_description::well_known::Rfc3339).map_err(
+        parse(&s)
+    }
+
+    fn parse<E: serde::de::Error>(s: &str) -> Result<time::OffsetDateTime, E> {
+        time::OffsetDateTime::parse(s, &time::format_description::well_known::Rfc3339).map_err(
             |e| {
-                D::Error::custom(format!(
+                E::custom(format!(
                     "Could not parse string '{s}' as RFC3339 UTC format: {e}"
                 ))
             },
         )
     }
+
+    /// As the parent module, for an optional datetime
+    pub(crate) mod option {
+        pub(crate) fn serialize<S>(
+            t: &Option<time::OffsetDateTime>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            match t {
+                Some(t) => serializer.serialize_some(&super::format::<S::Error>(t)?),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub(crate) fn deserialize<'de, D>(t: D) -> Result<Option<time::OffsetDateTime>, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            use serde::Deserialize;
+
+            Option::<String>::deserialize(t)?
+                .map(|s| super::parse(&s))
+                .transpose()
+        }
+    }
 }
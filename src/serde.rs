@@ -1,10 +1,17 @@
 //! De/serialization functionality
 //! Requires feature `serde`
 
-use std::io::{BufRead, Write};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
 
-use crate::{cookie_store::StoreResult, Cookie, CookieStore};
+use crate::cookie_store::StoreResult;
+use crate::store_error::check_loaded;
+use crate::{Cookie, CookieStore, MergeReport, MergeStrategy, StoreError};
 
+mod file;
+#[cfg(feature = "serde_json")]
+pub mod journal;
 #[cfg(feature = "serde_json")]
 pub mod json;
 #[cfg(feature = "serde_ron")]
@@ -16,7 +23,7 @@ pub fn load<R, E, F>(reader: R, cookies_from_str: F) -> StoreResult<CookieStore>
 where
     R: BufRead,
     F: Fn(&str) -> Result<Vec<Cookie<'static>>, E>,
-    crate::Error: From<E>,
+    StoreError: From<E>,
 {
     load_from(reader, cookies_from_str, false)
 }
@@ -27,7 +34,7 @@ pub fn load_all<R, E, F>(reader: R, cookies_from_str: F) -> StoreResult<CookieSt
 where
     R: BufRead,
     F: Fn(&str) -> Result<Vec<Cookie<'static>>, E>,
-    crate::Error: From<E>,
+    StoreError: From<E>,
 {
     load_from(reader, cookies_from_str, true)
 }
@@ -40,12 +47,41 @@ fn load_from<R, E, F>(
 where
     R: BufRead,
     F: Fn(&str) -> Result<Vec<Cookie<'static>>, E>,
-    crate::Error: From<E>,
+    StoreError: From<E>,
 {
     let mut cookie_store = String::new();
     reader.read_to_string(&mut cookie_store)?;
     let cookies = cookies_from_str(&cookie_store)?;
-    CookieStore::from_cookies(cookies.into_iter().map(Ok), include_expired)
+    CookieStore::from_cookies(
+        cookies
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, cookie)| check_loaded(index, cookie).transpose()),
+        include_expired,
+    )
+}
+
+/// Load cookies from `reader`, deserializing with `cookie_from_str`, and merge them into
+/// `cookie_store` per `strategy`; see [CookieStore::merge]. __Expired__ cookies are skipped.
+pub fn merge<R, E, F>(
+    cookie_store: &mut CookieStore,
+    mut reader: R,
+    strategy: &MergeStrategy,
+    cookies_from_str: F,
+) -> StoreResult<MergeReport>
+where
+    R: BufRead,
+    F: Fn(&str) -> Result<Vec<Cookie<'static>>, E>,
+    StoreError: From<E>,
+{
+    let mut cookies = String::new();
+    reader.read_to_string(&mut cookies)?;
+    let cookies = cookies_from_str(&cookies)?
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, cookie)| check_loaded(index, cookie).transpose())
+        .collect::<StoreResult<Vec<_>>>()?;
+    Ok(cookie_store.merge_cookies(cookies, strategy))
 }
 
 /// Serialize any __unexpired__ and __persistent__ cookies in the store with `cookie_to_string`
@@ -58,7 +94,7 @@ pub fn save<W, E, F>(
 where
     W: Write,
     F: Fn(&Vec<Cookie<'static>>) -> Result<String, E>,
-    crate::Error: From<E>,
+    StoreError: From<E>,
 {
     let mut cookies = Vec::new();
     for cookie in cookie_store.iter_unexpired() {
@@ -80,7 +116,7 @@ pub fn save_incl_expired_and_nonpersistent<W, E, F>(
 where
     W: Write,
     F: Fn(&Vec<Cookie<'static>>) -> Result<String, E>,
-    crate::Error: From<E>,
+    StoreError: From<E>,
 {
     let mut cookies = Vec::new();
     for cookie in cookie_store.iter_any() {
@@ -90,3 +126,72 @@ where
     writeln!(writer, "{}", cookies?)?;
     Ok(())
 }
+
+/// Load cookies from the file at `path`, deserializing with `cookies_from_str`, skipping any
+/// __expired__ cookies
+pub fn load_from_path<P, E, F>(path: P, cookies_from_str: F) -> StoreResult<CookieStore>
+where
+    P: AsRef<Path>,
+    F: Fn(&str) -> Result<Vec<Cookie<'static>>, E>,
+    StoreError: From<E>,
+{
+    load(BufReader::new(File::open(path)?), cookies_from_str)
+}
+
+/// Load cookies from the file at `path`, deserializing with `cookies_from_str`, loading both
+/// __unexpired__ and __expired__ cookies
+pub fn load_all_from_path<P, E, F>(path: P, cookies_from_str: F) -> StoreResult<CookieStore>
+where
+    P: AsRef<Path>,
+    F: Fn(&str) -> Result<Vec<Cookie<'static>>, E>,
+    StoreError: From<E>,
+{
+    load_all(BufReader::new(File::open(path)?), cookies_from_str)
+}
+
+/// Serialize any __unexpired__ and __persistent__ cookies in the store with `cookies_to_string`
+/// and atomically replace the file at `path` with the result.
+///
+/// The file is written to a `<path>.lock` sibling, which also serves as an advisory lock against
+/// concurrent saves, synced to disk, and then renamed over `path`; readers thus observe either the
+/// previous or the new contents, never a partial write. If `backups` is non-zero, the previous
+/// contents are retained as `<path>.1` (most recent) through `<path>.<backups>`. The new file
+/// keeps the permissions of the file it replaces.
+///
+/// If a save is interrupted, e.g. by a crash, the `<path>.lock` file may be left behind; later
+/// saves wait for it briefly, fail while it is recent, and remove it once it is more than 30
+/// seconds old. It is safe to delete by hand when no save is in progress.
+pub fn save_to_path<P, E, F>(
+    cookie_store: &CookieStore,
+    path: P,
+    backups: usize,
+    cookies_to_string: F,
+) -> StoreResult<()>
+where
+    P: AsRef<Path>,
+    F: Fn(&Vec<Cookie<'static>>) -> Result<String, E>,
+    StoreError: From<E>,
+{
+    file::write_atomic(path.as_ref(), backups, |writer| {
+        save(cookie_store, writer, cookies_to_string)
+    })
+}
+
+/// Serialize all (including __expired__ and __non-persistent__) cookies in the store with
+/// `cookies_to_string` and atomically replace the file at `path` with the result. See
+/// [save_to_path] for details on locking and `backups`.
+pub fn save_incl_expired_and_nonpersistent_to_path<P, E, F>(
+    cookie_store: &CookieStore,
+    path: P,
+    backups: usize,
+    cookies_to_string: F,
+) -> StoreResult<()>
+where
+    P: AsRef<Path>,
+    F: Fn(&Vec<Cookie<'static>>) -> Result<String, E>,
+    StoreError: From<E>,
+{
+    file::write_atomic(path.as_ref(), backups, |writer| {
+        save_incl_expired_and_nonpersistent(cookie_store, writer, cookies_to_string)
+    })
+}
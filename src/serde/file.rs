@@ -0,0 +1,257 @@
+//! Atomic, locked replacement of files on disk
+
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::cookie_store::StoreResult;
+
+/// How long to wait for a concurrent writer to release its lock before giving up
+const LOCK_TIMEOUT: Duration = Duration::from_secs(1);
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(10);
+/// A lock file last modified longer ago than this is assumed to have been left behind by a writer
+/// which crashed, and is removed
+const STALE_LOCK_AGE: Duration = Duration::from_secs(30);
+
+/// Returns `path` with `suffix` appended to its final component, e.g. `cookies.json.lock`
+pub(crate) fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut p: OsString = path.as_os_str().to_owned();
+    p.push(suffix);
+    p.into()
+}
+
+/// An advisory lock on a target file, taken by exclusively creating `<target>.lock`. The lock
+/// file doubles as the temporary file for the new contents, and is renamed over the target on
+/// [LockFile::commit]. If dropped without being committed, the lock file is removed.
+///
+/// A lock file left behind by a process which crashed mid-save is taken over once it is older
+/// than [STALE_LOCK_AGE]; it may also be deleted by hand. Waiters take over a stale lock one at a
+/// time, and only after confirming that the lock file they moved aside is the stale one; see
+/// [take_over_stale].
+pub(crate) struct LockFile {
+    path: PathBuf,
+    file: Option<File>,
+    committed: bool,
+}
+
+impl LockFile {
+    pub(crate) fn acquire(target: &Path) -> io::Result<LockFile> {
+        LockFile::acquire_with_stale_age(target, STALE_LOCK_AGE)
+    }
+
+    /// Acquire the lock on `target`, removing an existing lock file last modified more than
+    /// `stale_age` ago
+    pub(crate) fn acquire_with_stale_age(
+        target: &Path,
+        stale_age: Duration,
+    ) -> io::Result<LockFile> {
+        let path = with_suffix(target, ".lock");
+        let start = Instant::now();
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => {
+                    return Ok(LockFile {
+                        path,
+                        file: Some(file),
+                        committed: false,
+                    })
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if take_over_stale(&path, stale_age)? {
+                        continue;
+                    }
+                    if start.elapsed() >= LOCK_TIMEOUT {
+                        return Err(io::Error::new(
+                            e.kind(),
+                            format!(
+                                "unable to acquire lock '{}'; another process may be writing, or the lock file is stale",
+                                path.display()
+                            ),
+                        ));
+                    }
+                    std::thread::sleep(LOCK_RETRY_INTERVAL);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    pub(crate) fn file(&mut self) -> &mut File {
+        self.file
+            .as_mut()
+            .expect("LockFile file is only taken on commit")
+    }
+
+    /// Returns true if the lock file is still the one this `LockFile` created, i.e. it has not
+    /// been taken over as stale by another writer
+    #[cfg(unix)]
+    fn is_held(&self) -> bool {
+        use std::os::unix::fs::MetadataExt;
+        let held = self.file.as_ref().and_then(|file| file.metadata().ok());
+        match (held, fs::metadata(&self.path)) {
+            (Some(held), Ok(current)) => held.dev() == current.dev() && held.ino() == current.ino(),
+            _ => false,
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn is_held(&self) -> bool {
+        true
+    }
+
+    /// Sync the lock file to disk and rename it over `target`, releasing the lock
+    pub(crate) fn commit(mut self, target: &Path) -> io::Result<()> {
+        if !self.is_held() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("lock '{}' was taken over as stale", self.path.display()),
+            ));
+        }
+        if let Some(file) = self.file.take() {
+            file.sync_all()?;
+        }
+        fs::rename(&self.path, target)?;
+        self.committed = true;
+        sync_parent_dir(target)
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        if !self.committed && self.is_held() {
+            self.file.take();
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Removes the file at its path when dropped
+struct RemoveOnDrop(PathBuf);
+
+impl Drop for RemoveOnDrop {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Distinguishes the names under which stale locks are moved aside within this process
+static CLAIM_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Remove the lock file at `path` if it was last modified more than `stale_age` ago, returning
+/// true if the caller should retry acquiring the lock.
+///
+/// Removing the lock by path after checking its age would race with other waiters: one may
+/// remove the stale lock and create its own, which another, having also seen the stale lock,
+/// then removes. Instead, waiters take over one at a time, holding `<path>.takeover`, and move
+/// the lock aside atomically before checking that it is the stale one; a live lock moved aside
+/// is restored. Should a live lock nonetheless be lost, its owner finds so on commit.
+fn take_over_stale(path: &Path, stale_age: Duration) -> io::Result<bool> {
+    if !is_stale(path, stale_age) {
+        return Ok(false);
+    }
+    let guard_path = with_suffix(path, ".takeover");
+    let _guard = match OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&guard_path)
+    {
+        Ok(_) => RemoveOnDrop(guard_path),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            // the guard is held only momentarily, so an old one was left by a crashed process
+            if is_stale(&guard_path, stale_age) {
+                let _ = fs::remove_file(&guard_path);
+            }
+            return Ok(false);
+        }
+        Err(e) => return Err(e),
+    };
+    let claimed = with_suffix(
+        path,
+        &format!(
+            ".stale-{}-{}",
+            std::process::id(),
+            CLAIM_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ),
+    );
+    match fs::rename(path, &claimed) {
+        // released, or taken over by a waiter which held the guard before us
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(true),
+        Err(e) => return Err(e),
+        Ok(()) => {}
+    }
+    if is_stale(&claimed, stale_age) {
+        fs::remove_file(&claimed)?;
+        return Ok(true);
+    }
+    // the lock was replaced by a live one since it was checked
+    let restored = fs::hard_link(&claimed, path);
+    fs::remove_file(&claimed)?;
+    match restored {
+        Err(e) if e.kind() != io::ErrorKind::AlreadyExists => Err(e),
+        _ => Ok(false),
+    }
+}
+
+/// Returns true if the lock file at `path` was last modified more than `stale_age` ago
+fn is_stale(path: &Path, stale_age: Duration) -> bool {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .map_or(false, |age| age >= stale_age)
+}
+
+/// Ensure a completed rename is durable by syncing the containing directory
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => File::open(parent)?.sync_all(),
+        _ => File::open(".")?.sync_all(),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Shift `<path>.1` .. `<path>.<backups - 1>` up by one generation and copy the current contents
+/// of `path` to `<path>.1`. The oldest generation is overwritten.
+fn rotate_backups(path: &Path, backups: usize) -> io::Result<()> {
+    if backups == 0 || !path.exists() {
+        return Ok(());
+    }
+    for generation in (1..backups).rev() {
+        let from = with_suffix(path, &format!(".{generation}"));
+        if from.exists() {
+            fs::rename(&from, with_suffix(path, &format!(".{}", generation + 1)))?;
+        }
+    }
+    fs::copy(path, with_suffix(path, ".1"))?;
+    Ok(())
+}
+
+/// Atomically replace the file at `path` with the output of `write`, holding the advisory lock
+/// for the duration and retaining up to `backups` previous generations.
+pub(crate) fn write_atomic<F>(path: &Path, backups: usize, write: F) -> StoreResult<()>
+where
+    F: FnOnce(&mut BufWriter<&mut File>) -> StoreResult<()>,
+{
+    let mut lock = LockFile::acquire(path)?;
+    {
+        let mut writer = BufWriter::new(lock.file());
+        write(&mut writer)?;
+        writer.flush()?;
+    }
+    // the replacement keeps the permissions of the file it replaces, e.g. a private cookie file
+    // remains private
+    if let Ok(metadata) = fs::metadata(path) {
+        lock.file().set_permissions(metadata.permissions())?;
+    }
+    rotate_backups(path, backups)?;
+    lock.commit(path)?;
+    Ok(())
+}
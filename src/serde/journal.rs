@@ -0,0 +1,587 @@
+//! Append-only journal persistence via the JSON format
+//! Requires feature `serde_json`
+//!
+//! A [JournaledCookieStore] wraps a [CookieStore], appending one JSON line to a journal file for
+//! each change made through it, so that the store survives a crash without rewriting the entire
+//! jar on every change. On [JournaledCookieStore::open] the journal is replayed to rebuild the
+//! store; once enough entries have been appended, the journal is compacted into a snapshot
+//! holding a single entry per __unexpired__ and __persistent__ cookie.
+//!
+//! As with [save](crate::serde::json::save), __non-persistent__ cookies do not survive a restart:
+//! they are journaled while the store is in use, but dropped when the journal is replayed.
+//!
+//! A journal is intended to have a single writer; changes appended by another process while this
+//! one compacts the journal will be lost.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+use cookie::Cookie as RawCookie;
+use log::debug;
+use serde_derive::{Deserialize, Serialize};
+use url::Url;
+
+use crate::cookie_store::{InsertResult, StoreResult};
+use crate::{ChangeCause, Cookie, CookieStore, StoreAction, StoreError};
+
+/// Number of appended entries after which the journal is compacted, unless configured otherwise
+/// via [JournaledCookieStore::with_compaction_threshold]
+pub const DEFAULT_COMPACTION_THRESHOLD: usize = 1024;
+
+/// A single change to the store, as recorded in the journal
+#[derive(Serialize, Deserialize)]
+enum Entry {
+    Inserted(Cookie<'static>),
+    UpdatedExisting(Cookie<'static>),
+    ExpiredExisting {
+        domain: String,
+        path: String,
+        name: String,
+    },
+    Removed {
+        domain: String,
+        path: String,
+        name: String,
+    },
+    Cleared,
+}
+
+impl Entry {
+    fn apply(self, store: &mut CookieStore) {
+        match self {
+            Entry::Inserted(cookie) | Entry::UpdatedExisting(cookie) => {
+                store.store_cookie(cookie);
+            }
+            Entry::ExpiredExisting { domain, path, name } => {
+                store.expire_cookie(&domain, &path, &name, None, ChangeCause::ExpiredExisting);
+            }
+            Entry::Removed { domain, path, name } => {
+                store.remove(&domain, &path, &name);
+            }
+            Entry::Cleared => store.clear(),
+        }
+    }
+}
+
+/// A [CookieStore] whose changes are persisted to an append-only journal file. Read access to the
+/// underlying store is available via `Deref`; all changes must be made through the methods of
+/// this type so that they are journaled.
+#[derive(Debug)]
+pub struct JournaledCookieStore {
+    store: CookieStore,
+    path: PathBuf,
+    journal: File,
+    /// Entries appended to the journal since the last compaction
+    appended: usize,
+    compaction_threshold: usize,
+}
+
+impl JournaledCookieStore {
+    /// Open the journal at `path`, creating it if it does not exist, and rebuild the store by
+    /// replaying its entries. A final entry left incomplete by a crash is discarded, and truncated
+    /// from the journal so that subsequent entries are appended after the last complete one.
+    pub fn open<P: AsRef<Path>>(path: P) -> StoreResult<JournaledCookieStore> {
+        JournaledCookieStore::open_with(path, CookieStore::default())
+    }
+
+    /// As [JournaledCookieStore::open], replaying the journal into the provided, typically empty,
+    /// `store`, and then ending its session (see [CookieStore::end_session]); this allows configuring the store (e.g. with
+    /// [CookieStore::with_suffix_list]) before any changes are made.
+    pub fn open_with<P: AsRef<Path>>(
+        path: P,
+        mut store: CookieStore,
+    ) -> StoreResult<JournaledCookieStore> {
+        let path = path.as_ref().to_owned();
+        let mut entries = 0;
+        // the length of the journal up to the end of the last complete entry
+        let mut valid_len = 0;
+        let mut needs_newline = false;
+        match File::open(&path) {
+            Ok(file) => {
+                let mut reader = BufReader::new(file);
+                let mut line = String::new();
+                loop {
+                    line.clear();
+                    let read = reader.read_line(&mut line)?;
+                    if read == 0 {
+                        break;
+                    }
+                    let line_number = entries + 1;
+                    match serde_json::from_str::<Entry>(&line) {
+                        Ok(entry) => {
+                            entry.apply(&mut store);
+                            entries += 1;
+                            valid_len += read as u64;
+                            needs_newline = !line.ends_with('\n');
+                        }
+                        Err(e) if reader.fill_buf()?.is_empty() => {
+                            debug!("discarding incomplete final journal entry: {e}");
+                        }
+                        Err(e) => return Err(StoreError::from(e).at_line(line_number)),
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        let mut journal = OpenOptions::new().create(true).append(true).open(&path)?;
+        if journal.metadata()?.len() > valid_len {
+            journal.set_len(valid_len)?;
+        }
+        if needs_newline {
+            journal.write_all(b"\n")?;
+        }
+        store.end_session();
+        // entries beyond one per remaining cookie are those a compaction would discard
+        let appended = entries.saturating_sub(store.iter_any().count());
+        Ok(JournaledCookieStore {
+            store,
+            path,
+            journal,
+            appended,
+            compaction_threshold: DEFAULT_COMPACTION_THRESHOLD,
+        })
+    }
+
+    /// Compact the journal once more than `threshold` entries have been appended since it was
+    /// last compacted. A `threshold` of `0`
+    /// disables automatic compaction; [JournaledCookieStore::compact] may still be called
+    /// explicitly.
+    pub fn with_compaction_threshold(self, threshold: usize) -> JournaledCookieStore {
+        JournaledCookieStore {
+            compaction_threshold: threshold,
+            ..self
+        }
+    }
+
+    /// Atomically rewrite the journal as a snapshot of the __unexpired__ and __persistent__
+    /// cookies currently in the store.
+    pub fn compact(&mut self) -> StoreResult<()> {
+        super::file::write_atomic(&self.path, 0, |writer| {
+            for cookie in self.store.iter_unexpired().filter(|c| c.is_persistent()) {
+                serde_json::to_writer(&mut *writer, &Entry::Inserted(cookie.clone()))?;
+                writeln!(writer)?;
+            }
+            Ok(())
+        })?;
+        // the previous handle refers to the journal file which was just replaced
+        self.journal = OpenOptions::new().append(true).open(&self.path)?;
+        self.appended = 0;
+        Ok(())
+    }
+
+    /// Parses a new `Cookie` from `cookie_str` and inserts it into the store, journaling the
+    /// change. The outer `Result` indicates failure to write the journal; see
+    /// [CookieStore::parse].
+    pub fn parse(&mut self, cookie_str: &str, request_url: &Url) -> StoreResult<InsertResult> {
+        match Cookie::parse_at(cookie_str, request_url, self.store.now()) {
+            Ok(cookie) => self.insert(cookie.into_owned(), request_url),
+            Err(e) => Ok(Err(e)),
+        }
+    }
+
+    /// Converts a `cookie::Cookie` into a `cookie_store::Cookie` and inserts it into the store,
+    /// journaling the change. The outer `Result` indicates failure to write the journal; see
+    /// [CookieStore::insert_raw].
+    pub fn insert_raw(
+        &mut self,
+        cookie: &RawCookie<'_>,
+        request_url: &Url,
+    ) -> StoreResult<InsertResult> {
+        match Cookie::try_from_raw_cookie_at(cookie, request_url, self.store.now()) {
+            Ok(cookie) => self.insert(cookie.into_owned(), request_url),
+            Err(e) => Ok(Err(e)),
+        }
+    }
+
+    /// Inserts `cookie`, received from `request_url`, into the store, journaling the change. The
+    /// outer `Result` indicates failure to write the journal; see [CookieStore::insert].
+    pub fn insert(
+        &mut self,
+        cookie: Cookie<'static>,
+        request_url: &Url,
+    ) -> StoreResult<InsertResult> {
+        let domain = String::from(&cookie.domain);
+        let path = String::from(&cookie.path);
+        let name = cookie.name().to_owned();
+        let evictable = self.store.evictable(&domain);
+        let result = self.store.insert(cookie, request_url);
+        let entry = match result {
+            Ok(StoreAction::ExpiredExisting) => Some(Entry::ExpiredExisting {
+                domain: domain.clone(),
+                path,
+                name,
+            }),
+            Ok(ref action) => self.store.get_any(&domain, &path, &name).map(|cookie| {
+                if *action == StoreAction::Inserted {
+                    Entry::Inserted(cookie.clone())
+                } else {
+                    Entry::UpdatedExisting(cookie.clone())
+                }
+            }),
+            Err(_) => None,
+        };
+        if let Some(entry) = entry {
+            self.append(entry)?;
+        }
+        // cookies evicted to make room, per CookieStore::with_max_cookies_per_domain
+        if result == Ok(StoreAction::Inserted) {
+            for (path, name) in evictable.unwrap_or_default() {
+                if !self.store.contains_any(&domain, &path, &name) {
+                    self.append(Entry::Removed {
+                        domain: domain.clone(),
+                        path,
+                        name,
+                    })?;
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Store the `cookies` received from `url`, journaling each change. See
+    /// [CookieStore::store_response_cookies].
+    pub fn store_response_cookies<I: Iterator<Item = RawCookie<'static>>>(
+        &mut self,
+        cookies: I,
+        url: &Url,
+    ) -> StoreResult<()> {
+        for cookie in cookies {
+            if let Err(e) = self.insert_raw(&cookie, url)? {
+                debug!("unable to store Set-Cookie: {e:?}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes a `Cookie` from the store, journaling the change and returning the `Cookie` if it
+    /// was in the store
+    pub fn remove(
+        &mut self,
+        domain: &str,
+        path: &str,
+        name: &str,
+    ) -> StoreResult<Option<Cookie<'static>>> {
+        let removed = self.store.remove(domain, path, name);
+        if removed.is_some() {
+            self.append(Entry::Removed {
+                domain: domain.to_owned(),
+                path: path.to_owned(),
+                name: name.to_owned(),
+            })?;
+        }
+        Ok(removed)
+    }
+
+    /// Clear the contents of the store, journaling the change
+    pub fn clear(&mut self) -> StoreResult<()> {
+        self.store.clear();
+        self.append(Entry::Cleared)
+    }
+
+    /// Consume the `JournaledCookieStore`, returning the underlying `CookieStore`
+    pub fn into_inner(self) -> CookieStore {
+        self.store
+    }
+
+    fn append(&mut self, entry: Entry) -> StoreResult<()> {
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+        // a single write per entry, so a crash leaves at most the final entry incomplete
+        self.journal.write_all(line.as_bytes())?;
+        self.appended += 1;
+        if self.compaction_threshold > 0 && self.appended > self.compaction_threshold {
+            self.compact()?;
+        }
+        Ok(())
+    }
+}
+
+impl Deref for JournaledCookieStore {
+    type Target = CookieStore;
+    fn deref(&self) -> &Self::Target {
+        &self.store
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::JournaledCookieStore;
+    use crate::utils::test as test_utils;
+    use crate::StoreAction;
+
+    fn line_count(path: &std::path::Path) -> usize {
+        std::fs::read_to_string(path).unwrap().lines().count()
+    }
+
+    #[test]
+    fn replay() {
+        let path = test_utils::temp_path("journal_replay");
+        let url = test_utils::url("http://example.com/foo/bar");
+        {
+            let mut store = JournaledCookieStore::open(&path).unwrap();
+            assert_eq!(
+                Ok(StoreAction::Inserted),
+                store.parse("cookie1=value1", &url).unwrap()
+            );
+            assert_eq!(
+                Ok(StoreAction::Inserted),
+                store.parse("cookie2=value2; Max-Age=60", &url).unwrap()
+            );
+            assert_eq!(
+                Ok(StoreAction::UpdatedExisting),
+                store.parse("cookie2=value3; Max-Age=60", &url).unwrap()
+            );
+            assert_eq!(
+                Ok(StoreAction::Inserted),
+                store.parse("cookie3=value3", &url).unwrap()
+            );
+            assert_eq!(
+                Ok(StoreAction::ExpiredExisting),
+                store.parse("cookie3=value3; Max-Age=0", &url).unwrap()
+            );
+            assert!(store
+                .remove("example.com", "/foo", "cookie1")
+                .unwrap()
+                .is_some());
+            // rejected cookies and no-op removals are not journaled
+            assert!(store
+                .parse("cookie4=value4; Domain=example.org", &url)
+                .unwrap()
+                .is_err());
+            assert!(store
+                .remove("example.com", "/foo", "cookie1")
+                .unwrap()
+                .is_none());
+        }
+        assert_eq!(6, line_count(&path));
+
+        let store = JournaledCookieStore::open(&path).unwrap();
+        assert!(store.get_any("example.com", "/foo", "cookie1").is_none());
+        assert_eq!(
+            "value3",
+            store.get("example.com", "/foo", "cookie2").unwrap().value()
+        );
+        assert!(store
+            .get_any("example.com", "/foo", "cookie3")
+            .unwrap()
+            .is_expired());
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn incomplete_final_entry() {
+        let path = test_utils::temp_path("journal_incomplete_final_entry");
+        let url = test_utils::url("http://example.com/foo/bar");
+        {
+            let mut store = JournaledCookieStore::open(&path).unwrap();
+            store
+                .parse("cookie1=value1; Max-Age=60", &url)
+                .unwrap()
+                .unwrap();
+            store.clear().unwrap();
+            store
+                .parse("cookie2=value2; Max-Age=60", &url)
+                .unwrap()
+                .unwrap();
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b"{\"Inserted\":{\"raw_cookie\":\"cookie3=va")
+            .unwrap();
+
+        let store = JournaledCookieStore::open(&path).unwrap();
+        assert!(store.get("example.com", "/foo", "cookie1").is_none());
+        assert!(store.get("example.com", "/foo", "cookie2").is_some());
+        assert!(store.get("example.com", "/foo", "cookie3").is_none());
+        drop(store);
+
+        // entries appended after the incomplete one are not lost
+        {
+            let mut store = JournaledCookieStore::open(&path).unwrap();
+            store
+                .parse("cookie4=value4; Max-Age=60", &url)
+                .unwrap()
+                .unwrap();
+            store
+                .parse("cookie5=value5; Max-Age=60", &url)
+                .unwrap()
+                .unwrap();
+        }
+        assert_eq!(5, line_count(&path));
+        let store = JournaledCookieStore::open(&path).unwrap();
+        assert!(store.get("example.com", "/foo", "cookie2").is_some());
+        assert!(store.get("example.com", "/foo", "cookie4").is_some());
+        assert!(store.get("example.com", "/foo", "cookie5").is_some());
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn compaction() {
+        let path = test_utils::temp_path("journal_compaction");
+        let url = test_utils::url("http://example.com/foo/bar");
+        let mut store = JournaledCookieStore::open(&path)
+            .unwrap()
+            .with_compaction_threshold(4);
+        for i in 0..4 {
+            store
+                .parse(&format!("cookie1=value{i}; Max-Age=60"), &url)
+                .unwrap()
+                .unwrap();
+        }
+        assert_eq!(4, line_count(&path));
+        // exceeding the threshold compacts down to a single entry per cookie
+        store
+            .parse("cookie2=value2; Max-Age=60", &url)
+            .unwrap()
+            .unwrap();
+        assert_eq!(2, line_count(&path));
+        // appends continue against the compacted journal
+        store
+            .parse("cookie3=value3; Max-Age=60", &url)
+            .unwrap()
+            .unwrap();
+        assert_eq!(3, line_count(&path));
+        drop(store);
+
+        let store = JournaledCookieStore::open(&path).unwrap();
+        assert_eq!(
+            "value3",
+            store.get("example.com", "/foo", "cookie1").unwrap().value()
+        );
+        assert!(store.get("example.com", "/foo", "cookie2").is_some());
+        assert!(store.get("example.com", "/foo", "cookie3").is_some());
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn compaction_of_large_jar() {
+        let path = test_utils::temp_path("journal_compaction_of_large_jar");
+        let url = test_utils::url("http://example.com/foo/bar");
+        let mut store = JournaledCookieStore::open(&path)
+            .unwrap()
+            .with_compaction_threshold(4);
+        for i in 0..8 {
+            store
+                .parse(&format!("cookie{i}=value{i}; Max-Age=60"), &url)
+                .unwrap()
+                .unwrap();
+        }
+        // compacted after the fifth insert, with three appended since
+        assert_eq!(8, line_count(&path));
+        store.compact().unwrap();
+        assert_eq!(8, line_count(&path));
+        // holding more cookies than the threshold does not compact on every change; an update
+        // appends a line, where a compaction would leave a single entry per cookie
+        store
+            .parse("cookie0=updated; Max-Age=60", &url)
+            .unwrap()
+            .unwrap();
+        assert_eq!(9, line_count(&path));
+        drop(store);
+
+        // nor does reopening a compacted journal
+        let mut store = JournaledCookieStore::open(&path)
+            .unwrap()
+            .with_compaction_threshold(4);
+        store
+            .parse("cookie1=updated; Max-Age=60", &url)
+            .unwrap()
+            .unwrap();
+        assert_eq!(10, line_count(&path));
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn session_cookies() {
+        let path = test_utils::temp_path("journal_session_cookies");
+        let url = test_utils::url("http://example.com/foo/bar");
+        {
+            let mut store = JournaledCookieStore::open(&path).unwrap();
+            store.parse("session=value1", &url).unwrap().unwrap();
+            store
+                .parse("persistent=value2; Max-Age=60", &url)
+                .unwrap()
+                .unwrap();
+            assert!(store.contains("example.com", "/foo", "session"));
+        }
+        // session cookies are dropped on replay...
+        let mut store = JournaledCookieStore::open(&path).unwrap();
+        assert!(!store.contains_any("example.com", "/foo", "session"));
+        assert!(store.contains("example.com", "/foo", "persistent"));
+        store.parse("session=value3", &url).unwrap().unwrap();
+        // ...and left out of snapshots
+        store.compact().unwrap();
+        assert_eq!(1, line_count(&path));
+        assert!(store.contains("example.com", "/foo", "session"));
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn clock() {
+        let path = test_utils::temp_path("journal_clock");
+        let url = test_utils::url("http://example.com/foo/bar");
+        let start = time::OffsetDateTime::UNIX_EPOCH;
+        let clock = crate::ManualClock::new(start);
+        let config = crate::CookieStore::default().with_clock(clock.clone());
+        let mut store = JournaledCookieStore::open_with(&path, config).unwrap();
+        store.parse("parsed=1; Max-Age=60", &url).unwrap().unwrap();
+        store
+            .insert_raw(&cookie::Cookie::parse("raw=2; Max-Age=120").unwrap(), &url)
+            .unwrap()
+            .unwrap();
+        let expires = |name| {
+            store
+                .get_any("example.com", "/foo", name)
+                .unwrap()
+                .expires
+                .clone()
+        };
+        assert_eq!(
+            crate::CookieExpiration::AtUtc(start + time::Duration::seconds(60)),
+            expires("parsed")
+        );
+        assert_eq!(
+            crate::CookieExpiration::AtUtc(start + time::Duration::seconds(120)),
+            expires("raw")
+        );
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn eviction() {
+        let path = test_utils::temp_path("journal_eviction");
+        let url = test_utils::url("http://example.com/foo/bar");
+        let config = crate::CookieStore::default().with_max_cookies_per_domain(1);
+        let mut store = JournaledCookieStore::open_with(&path, config).unwrap();
+        store
+            .parse("cookie1=value1; Max-Age=60", &url)
+            .unwrap()
+            .unwrap();
+        store
+            .parse("cookie2=value2; Max-Age=60", &url)
+            .unwrap()
+            .unwrap();
+        // the eviction of cookie1 is journaled
+        assert_eq!(3, line_count(&path));
+        drop(store);
+
+        let store = JournaledCookieStore::open(&path).unwrap();
+        assert!(!store.contains_any("example.com", "/foo", "cookie1"));
+        assert!(store.contains("example.com", "/foo", "cookie2"));
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -2,8 +2,10 @@
 //! Requires feature `serde_json`
 
 use std::io::{BufRead, Write};
+use std::path::Path;
 
 use crate::cookie_store::{CookieStore, StoreResult};
+use crate::{MergeReport, MergeStrategy};
 
 /// Load JSON-formatted cookies from `reader`, skipping any __expired__ cookies.
 /// __NB__: This function is not compatible with data produced by [CookieStore::save_json] or
@@ -19,6 +21,18 @@ pub fn load_all<R: BufRead>(reader: R) -> StoreResult<CookieStore> {
     super::load_all(reader, |cookies| serde_json::from_str(cookies))
 }
 
+/// Load JSON-formatted cookies from `reader` and merge them into `cookie_store` per `strategy`,
+/// skipping any __expired__ cookies. See [super::merge].
+pub fn merge<R: BufRead>(
+    cookie_store: &mut CookieStore,
+    reader: R,
+    strategy: &MergeStrategy,
+) -> StoreResult<MergeReport> {
+    super::merge(cookie_store, reader, strategy, |cookies| {
+        serde_json::from_str(cookies)
+    })
+}
+
 /// Serialize any __unexpired__ and __persistent__ cookies in the store to JSON format and
 /// write them to `writer`.
 /// __NB__: This function does not produce data compatible with [CookieStore::load_json] or
@@ -37,6 +51,44 @@ pub fn save_incl_expired_and_nonpersistent<W: Write>(
     super::save_incl_expired_and_nonpersistent(cookie_store, writer, ::serde_json::to_string_pretty)
 }
 
+/// Load JSON-formatted cookies from the file at `path`, skipping any __expired__ cookies.
+pub fn load_from_path<P: AsRef<Path>>(path: P) -> StoreResult<CookieStore> {
+    super::load_from_path(path, |cookies| serde_json::from_str(cookies))
+}
+
+/// Load JSON-formatted cookies from the file at `path`, loading both __expired__ and
+/// __unexpired__ cookies.
+pub fn load_all_from_path<P: AsRef<Path>>(path: P) -> StoreResult<CookieStore> {
+    super::load_all_from_path(path, |cookies| serde_json::from_str(cookies))
+}
+
+/// Serialize any __unexpired__ and __persistent__ cookies in the store to JSON format and
+/// atomically replace the file at `path`, keeping up to `backups` previous generations. See
+/// [super::save_to_path].
+pub fn save_to_path<P: AsRef<Path>>(
+    cookie_store: &CookieStore,
+    path: P,
+    backups: usize,
+) -> StoreResult<()> {
+    super::save_to_path(cookie_store, path, backups, ::serde_json::to_string_pretty)
+}
+
+/// Serialize all (including __expired__ and __non-persistent__) cookies in the store to JSON
+/// format and atomically replace the file at `path`, keeping up to `backups` previous
+/// generations. See [super::save_to_path].
+pub fn save_incl_expired_and_nonpersistent_to_path<P: AsRef<Path>>(
+    cookie_store: &CookieStore,
+    path: P,
+    backups: usize,
+) -> StoreResult<()> {
+    super::save_incl_expired_and_nonpersistent_to_path(
+        cookie_store,
+        path,
+        backups,
+        ::serde_json::to_string_pretty,
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::BufWriter;
@@ -44,6 +96,9 @@ mod tests {
     use super::{save, save_incl_expired_and_nonpersistent};
 
     use super::{load, load_all};
+    use super::{load_from_path, merge, save_to_path};
+    use crate::utils::test::temp_path;
+    use crate::{CookieStore, MergeChoice, MergeStrategy};
 
     fn cookie() -> String {
         r#"[
@@ -150,4 +205,169 @@ mod tests {
         let string = String::from_utf8(writer.into_inner().unwrap()).unwrap();
         assert_eq!(cookie, string);
     }
+
+    #[test]
+    fn save_load_path() {
+        let path = temp_path("json_save_load_path");
+        let cookie = cookie();
+        let cookie_store = load(Into::<&[u8]>::into(cookie.as_bytes())).unwrap();
+
+        save_to_path(&cookie_store, &path, 2).unwrap();
+        assert_eq!(cookie, std::fs::read_to_string(&path).unwrap());
+        assert!(!crate::serde::file::with_suffix(&path, ".lock").exists());
+        assert!(!crate::serde::file::with_suffix(&path, ".1").exists());
+
+        let loaded = load_from_path(&path).unwrap();
+        assert_eq!(loaded.iter_unexpired().map(|_| 1).sum::<i32>(), 1);
+
+        // each subsequent save shifts the previous contents into the backup generations
+        save_to_path(&loaded, &path, 2).unwrap();
+        save_to_path(&crate::CookieStore::default(), &path, 2).unwrap();
+        save_to_path(&crate::CookieStore::default(), &path, 2).unwrap();
+        assert_eq!("[]\n", std::fs::read_to_string(&path).unwrap());
+        let backup = |n: usize| crate::serde::file::with_suffix(&path, &format!(".{n}"));
+        assert_eq!("[]\n", std::fs::read_to_string(backup(1)).unwrap());
+        assert_eq!(cookie, std::fs::read_to_string(backup(2)).unwrap());
+        assert!(!backup(3).exists());
+
+        // a held lock blocks concurrent saves
+        let lock = crate::serde::file::LockFile::acquire(&path).unwrap();
+        assert!(save_to_path(&loaded, &path, 2).is_err());
+        drop(lock);
+        save_to_path(&loaded, &path, 2).unwrap();
+        assert_eq!(cookie, std::fs::read_to_string(&path).unwrap());
+
+        // a lock left behind by a crashed writer is taken over once stale
+        std::mem::forget(crate::serde::file::LockFile::acquire(&path).unwrap());
+        assert!(save_to_path(&loaded, &path, 2).is_err());
+        drop(
+            crate::serde::file::LockFile::acquire_with_stale_age(&path, std::time::Duration::ZERO)
+                .unwrap(),
+        );
+        save_to_path(&loaded, &path, 2).unwrap();
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn stale_lock_race() {
+        use std::io::Write;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::{Arc, Barrier};
+        use std::time::Duration;
+
+        use crate::serde::file::{with_suffix, LockFile};
+
+        let path = temp_path("json_stale_lock_race");
+        let stale_age = Duration::from_millis(100);
+        let threads = 16;
+        for _ in 0..5 {
+            // a lock left behind by a crashed writer, which all waiters find stale at once
+            std::fs::write(with_suffix(&path, ".lock"), "partial").unwrap();
+            std::thread::sleep(stale_age + Duration::from_millis(20));
+
+            let holders = Arc::new(AtomicUsize::new(0));
+            let barrier = Arc::new(Barrier::new(threads));
+            let writers = (0..threads)
+                .map(|i| {
+                    let path = path.clone();
+                    let holders = holders.clone();
+                    let barrier = barrier.clone();
+                    std::thread::spawn(move || {
+                        barrier.wait();
+                        let mut lock = LockFile::acquire_with_stale_age(&path, stale_age).unwrap();
+                        assert_eq!(0, holders.fetch_add(1, Ordering::SeqCst), "lock shared");
+                        writeln!(lock.file(), "writer {i}").unwrap();
+                        std::thread::sleep(Duration::from_millis(2));
+                        holders.fetch_sub(1, Ordering::SeqCst);
+                        lock.commit(&path).unwrap();
+                    })
+                })
+                .collect::<Vec<_>>();
+            for writer in writers {
+                writer.join().unwrap();
+            }
+            let contents = std::fs::read_to_string(&path).unwrap();
+            assert!(contents.starts_with("writer "), "{contents}");
+            assert_eq!(1, contents.lines().count());
+            // neither the lock, nor any stale lock moved aside, remains
+            assert_eq!(
+                1,
+                std::fs::read_dir(path.parent().unwrap()).unwrap().count()
+            );
+        }
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn save_to_path_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = temp_path("json_save_to_path_keeps_permissions");
+        let cookie_store = load(Into::<&[u8]>::into(cookie().as_bytes())).unwrap();
+        save_to_path(&cookie_store, &path, 0).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        save_to_path(&cookie_store, &path, 0).unwrap();
+        assert_eq!(
+            0o600,
+            std::fs::metadata(&path).unwrap().permissions().mode() & 0o777
+        );
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn merge_reader() {
+        let mut cookie_store = CookieStore::default();
+        let report = merge(
+            &mut cookie_store,
+            cookie().as_bytes(),
+            &MergeStrategy::KeepExisting,
+        )
+        .unwrap();
+        assert_eq!(1, report.added);
+        let report = merge(
+            &mut cookie_store,
+            cookie_expired().as_bytes(),
+            &MergeStrategy::KeepExisting,
+        )
+        .unwrap();
+        assert_eq!(1, report.skipped_expired);
+        let report = merge(
+            &mut cookie_store,
+            cookie().as_bytes(),
+            &MergeStrategy::KeepExisting,
+        )
+        .unwrap();
+        assert_eq!(0, report.added);
+        assert_eq!(1, report.conflicts.len());
+        assert_eq!(MergeChoice::Existing, report.conflicts[0].resolution);
+        assert_eq!(1, cookie_store.iter_any().count());
+    }
+
+    #[test]
+    fn creation_time() {
+        let mut cookie_store = CookieStore::default();
+        let url = crate::utils::test::url("https://example.com/");
+        cookie_store
+            .parse("cookie1=value1; Max-Age=3600", &url)
+            .unwrap();
+        let creation_time = cookie_store
+            .get("example.com", "/", "cookie1")
+            .unwrap()
+            .creation_time();
+        assert!(creation_time.is_some());
+
+        let mut writer = BufWriter::new(Vec::new());
+        save(&cookie_store, &mut writer).unwrap();
+        let string = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert!(string.contains("\"creation_time\""));
+        let loaded = load(string.as_bytes()).unwrap();
+        assert_eq!(
+            cookie_store.get("example.com", "/", "cookie1"),
+            loaded.get("example.com", "/", "cookie1")
+        );
+    }
 }
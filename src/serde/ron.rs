@@ -2,8 +2,10 @@
 //! Requires feature `serde_ron`
 
 use std::io::{BufRead, Write};
+use std::path::Path;
 
 use crate::cookie_store::{CookieStore, StoreResult};
+use crate::{MergeReport, MergeStrategy};
 
 /// Load RON-formatted cookies from `reader`, skipping any __expired__ cookies
 pub fn load<R: BufRead>(reader: R) -> StoreResult<CookieStore> {
@@ -15,6 +17,18 @@ pub fn load_all<R: BufRead>(reader: R) -> StoreResult<CookieStore> {
     super::load_all(reader, |cookies| ron::from_str(cookies))
 }
 
+/// Load RON-formatted cookies from `reader` and merge them into `cookie_store` per `strategy`,
+/// skipping any __expired__ cookies. See [super::merge].
+pub fn merge<R: BufRead>(
+    cookie_store: &mut CookieStore,
+    reader: R,
+    strategy: &MergeStrategy,
+) -> StoreResult<MergeReport> {
+    super::merge(cookie_store, reader, strategy, |cookies| {
+        ron::from_str(cookies)
+    })
+}
+
 /// Serialize any __unexpired__ and __persistent__ cookies in the store to JSON format and
 /// write them to `writer`
 pub fn save<W: Write>(cookie_store: &CookieStore, writer: &mut W) -> StoreResult<()> {
@@ -33,6 +47,43 @@ pub fn save_incl_expired_and_nonpersistent<W: Write>(
     })
 }
 
+/// Load RON-formatted cookies from the file at `path`, skipping any __expired__ cookies
+pub fn load_from_path<P: AsRef<Path>>(path: P) -> StoreResult<CookieStore> {
+    super::load_from_path(path, |cookies| ron::from_str(cookies))
+}
+
+/// Load RON-formatted cookies from the file at `path`, loading both __expired__ and
+/// __unexpired__ cookies
+pub fn load_all_from_path<P: AsRef<Path>>(path: P) -> StoreResult<CookieStore> {
+    super::load_all_from_path(path, |cookies| ron::from_str(cookies))
+}
+
+/// Serialize any __unexpired__ and __persistent__ cookies in the store to RON format and
+/// atomically replace the file at `path`, keeping up to `backups` previous generations. See
+/// [super::save_to_path].
+pub fn save_to_path<P: AsRef<Path>>(
+    cookie_store: &CookieStore,
+    path: P,
+    backups: usize,
+) -> StoreResult<()> {
+    super::save_to_path(cookie_store, path, backups, |string| {
+        ::ron::ser::to_string_pretty(string, ron::ser::PrettyConfig::default())
+    })
+}
+
+/// Serialize all (including __expired__ and __non-persistent__) cookies in the store to RON
+/// format and atomically replace the file at `path`, keeping up to `backups` previous
+/// generations. See [super::save_to_path].
+pub fn save_incl_expired_and_nonpersistent_to_path<P: AsRef<Path>>(
+    cookie_store: &CookieStore,
+    path: P,
+    backups: usize,
+) -> StoreResult<()> {
+    super::save_incl_expired_and_nonpersistent_to_path(cookie_store, path, backups, |string| {
+        ::ron::ser::to_string_pretty(string, ron::ser::PrettyConfig::default())
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::BufWriter;
@@ -131,4 +182,40 @@ mod tests {
         let string = String::from_utf8(writer.into_inner().unwrap()).unwrap();
         assert_eq!(cookie, string);
     }
+
+    #[test]
+    fn creation_time() {
+        let mut cookie_store = crate::CookieStore::default();
+        let url = crate::utils::test::url("https://example.com/");
+        cookie_store
+            .parse("cookie1=value1; Max-Age=3600", &url)
+            .unwrap();
+        let creation_time = cookie_store
+            .get("example.com", "/", "cookie1")
+            .unwrap()
+            .creation_time();
+        assert!(creation_time.is_some());
+
+        let mut writer = BufWriter::new(Vec::new());
+        save(&cookie_store, &mut writer).unwrap();
+        let string = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert!(string.contains("creation_time: Some("));
+        let loaded = load(string.as_bytes()).unwrap();
+        assert_eq!(
+            creation_time.map(|t| t.unix_timestamp()),
+            loaded
+                .get("example.com", "/", "cookie1")
+                .unwrap()
+                .creation_time()
+                .map(|t| t.unix_timestamp())
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        match load("[\n    (\n        raw_cookie: 1,\n".as_bytes()) {
+            Err(crate::StoreError::Ron { line, .. }) => assert_eq!(3, line),
+            r => panic!("unexpected {r:?}"),
+        }
+    }
 }
@@ -0,0 +1,386 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use cookie::SameSite;
+use time::OffsetDateTime;
+
+use crate::cookie::Cookie;
+use crate::cookie_expiration::CookieExpiration;
+
+/// A change to a single field of a `Cookie` present on both sides of a [`StoreDiff`]. Unless the
+/// `log_secure_cookie_values` feature is enabled, the `Display` output omits a changed value which
+/// is `sensitive`.
+#[derive(PartialEq, Clone, Debug, Eq)]
+pub enum FieldChange {
+    Value {
+        old: String,
+        new: String,
+        /// The `Cookie` is Secure or HttpOnly on either side
+        sensitive: bool,
+    },
+    Secure {
+        old: bool,
+        new: bool,
+    },
+    HttpOnly {
+        old: bool,
+        new: bool,
+    },
+    SameSite {
+        old: Option<SameSite>,
+        new: Option<SameSite>,
+    },
+    Expires {
+        old: CookieExpiration,
+        new: CookieExpiration,
+    },
+}
+
+/// How a `Cookie` differs between the two sides of a [`StoreDiff`]
+#[derive(PartialEq, Clone, Debug, Eq)]
+pub enum DiffKind {
+    /// The `Cookie` is __unexpired__ on the right, and absent (or __expired__) on the left
+    Added,
+    /// The `Cookie` is present on the left and absent on the right
+    Removed,
+    /// The `Cookie` is __unexpired__ on the left and __expired__ on the right
+    Expired,
+    /// The `Cookie` is __unexpired__ on both sides, with the given field changes
+    Modified(Vec<FieldChange>),
+}
+
+/// A `Cookie`, identified by domain, path, and name, which differs between the two sides of a
+/// [`StoreDiff`]
+#[derive(PartialEq, Clone, Debug, Eq)]
+pub struct DiffEntry {
+    pub domain: String,
+    pub path: String,
+    pub name: String,
+    pub kind: DiffKind,
+}
+
+/// The differences between two sets of cookies, e.g. two [`CookieStore`](crate::CookieStore)s
+/// (see [`CookieStore::diff`](crate::CookieStore::diff)), or a store and a snapshot of its
+/// cookies taken earlier. Entries are ordered by domain, path, then name. The `Display` impl
+/// renders one line per entry, prefixed by `+` (added), `-` (removed), `x` (expired), or `~`
+/// (modified).
+#[derive(PartialEq, Clone, Debug, Default, Eq)]
+pub struct StoreDiff {
+    pub entries: Vec<DiffEntry>,
+}
+
+type Key = (String, String, String);
+
+fn key(cookie: &Cookie<'_>) -> Key {
+    (
+        String::from(&cookie.domain),
+        String::from(&cookie.path),
+        cookie.name().to_owned(),
+    )
+}
+
+impl StoreDiff {
+    /// The differences from the cookies `left` to the cookies `right`, as of the current time
+    pub fn new<'a, L, R>(left: L, right: R) -> StoreDiff
+    where
+        L: IntoIterator<Item = &'a Cookie<'static>>,
+        R: IntoIterator<Item = &'a Cookie<'static>>,
+    {
+        StoreDiff::new_at(left, right, OffsetDateTime::now_utc())
+    }
+
+    /// The differences from the cookies `left` to the cookies `right`, with cookies considered
+    /// __expired__ as of `utc_tm`
+    pub fn new_at<'a, L, R>(left: L, right: R, utc_tm: OffsetDateTime) -> StoreDiff
+    where
+        L: IntoIterator<Item = &'a Cookie<'static>>,
+        R: IntoIterator<Item = &'a Cookie<'static>>,
+    {
+        let mut sides: BTreeMap<Key, (Option<&Cookie<'static>>, Option<&Cookie<'static>>)> =
+            BTreeMap::new();
+        for cookie in left {
+            sides.entry(key(cookie)).or_default().0 = Some(cookie);
+        }
+        for cookie in right {
+            sides.entry(key(cookie)).or_default().1 = Some(cookie);
+        }
+        let entries = sides
+            .into_iter()
+            .filter_map(|((domain, path, name), sides)| {
+                let live = |c: &&Cookie<'_>| !c.expires_by(&utc_tm);
+                let kind = match sides {
+                    (None, None) => None,
+                    (Some(_), None) => Some(DiffKind::Removed),
+                    (left, Some(right)) => match (left.filter(live), live(&right)) {
+                        (None, true) => Some(DiffKind::Added),
+                        (None, false) => None,
+                        (Some(_), false) => Some(DiffKind::Expired),
+                        (Some(left), true) => {
+                            let changes = field_changes(left, right);
+                            if changes.is_empty() {
+                                None
+                            } else {
+                                Some(DiffKind::Modified(changes))
+                            }
+                        }
+                    },
+                };
+                kind.map(|kind| DiffEntry {
+                    domain,
+                    path,
+                    name,
+                    kind,
+                })
+            })
+            .collect();
+        StoreDiff { entries }
+    }
+
+    /// Returns true if there are no differences
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn of_kind<'a>(&'a self, f: fn(&DiffKind) -> bool) -> impl Iterator<Item = &'a DiffEntry> + 'a {
+        self.entries.iter().filter(move |e| f(&e.kind))
+    }
+
+    /// The entries for cookies which were added
+    pub fn added(&self) -> impl Iterator<Item = &DiffEntry> {
+        self.of_kind(|k| matches!(k, DiffKind::Added))
+    }
+
+    /// The entries for cookies which were removed
+    pub fn removed(&self) -> impl Iterator<Item = &DiffEntry> {
+        self.of_kind(|k| matches!(k, DiffKind::Removed))
+    }
+
+    /// The entries for cookies which expired
+    pub fn expired(&self) -> impl Iterator<Item = &DiffEntry> {
+        self.of_kind(|k| matches!(k, DiffKind::Expired))
+    }
+
+    /// The entries for cookies which were modified
+    pub fn modified(&self) -> impl Iterator<Item = &DiffEntry> {
+        self.of_kind(|k| matches!(k, DiffKind::Modified(_)))
+    }
+}
+
+fn field_changes(left: &Cookie<'_>, right: &Cookie<'_>) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    if left.value() != right.value() {
+        let is_sensitive = |c: &Cookie<'_>| c.secure() == Some(true) || c.http_only() == Some(true);
+        changes.push(FieldChange::Value {
+            old: left.value().to_owned(),
+            new: right.value().to_owned(),
+            sensitive: is_sensitive(left) || is_sensitive(right),
+        });
+    }
+    let (old, new) = (
+        left.secure().unwrap_or(false),
+        right.secure().unwrap_or(false),
+    );
+    if old != new {
+        changes.push(FieldChange::Secure { old, new });
+    }
+    let (old, new) = (
+        left.http_only().unwrap_or(false),
+        right.http_only().unwrap_or(false),
+    );
+    if old != new {
+        changes.push(FieldChange::HttpOnly { old, new });
+    }
+    let (old, new) = (left.same_site(), right.same_site());
+    if old != new {
+        changes.push(FieldChange::SameSite { old, new });
+    }
+    if left.expires != right.expires {
+        changes.push(FieldChange::Expires {
+            old: left.expires.clone(),
+            new: right.expires.clone(),
+        });
+    }
+    changes
+}
+
+struct DisplayExpiration<'a>(&'a CookieExpiration);
+
+impl fmt::Display for DisplayExpiration<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self.0 {
+            CookieExpiration::AtUtc(ref utc_tm) => write!(f, "{utc_tm}"),
+            CookieExpiration::SessionEnd => f.write_str("session end"),
+        }
+    }
+}
+
+struct DisplaySameSite(Option<SameSite>);
+
+impl fmt::Display for DisplaySameSite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(same_site) => write!(f, "{same_site}"),
+            None => f.write_str("unset"),
+        }
+    }
+}
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            FieldChange::Value {
+                sensitive: true, ..
+            } if !cfg!(feature = "log_secure_cookie_values") => {
+                f.write_str("value [redacted] -> [redacted]")
+            }
+            FieldChange::Value {
+                ref old, ref new, ..
+            } => write!(f, "value {old:?} -> {new:?}"),
+            FieldChange::Secure { old, new } => write!(f, "Secure {old} -> {new}"),
+            FieldChange::HttpOnly { old, new } => write!(f, "HttpOnly {old} -> {new}"),
+            FieldChange::SameSite { old, new } => write!(
+                f,
+                "SameSite {} -> {}",
+                DisplaySameSite(old),
+                DisplaySameSite(new)
+            ),
+            FieldChange::Expires { ref old, ref new } => write!(
+                f,
+                "expires {} -> {}",
+                DisplayExpiration(old),
+                DisplayExpiration(new)
+            ),
+        }
+    }
+}
+
+impl fmt::Display for DiffEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prefix = match self.kind {
+            DiffKind::Added => '+',
+            DiffKind::Removed => '-',
+            DiffKind::Expired => 'x',
+            DiffKind::Modified(_) => '~',
+        };
+        write!(f, "{prefix} {}{} {}", self.domain, self.path, self.name)?;
+        if let DiffKind::Modified(ref changes) = self.kind {
+            for (i, change) in changes.iter().enumerate() {
+                f.write_str(if i == 0 { ": " } else { ", " })?;
+                write!(f, "{change}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for StoreDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{entry}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cookie::SameSite;
+
+    use super::{DiffKind, FieldChange, StoreDiff};
+    use crate::utils::test as test_utils;
+    use crate::CookieStore;
+
+    #[test]
+    fn diff() {
+        let url = test_utils::url("https://example.com/");
+        let mut before = CookieStore::default();
+        for cookie in [
+            "unchanged=1",
+            "removed=2",
+            "expired=3; Max-Age=3600",
+            "modified=4; Secure",
+        ] {
+            before.parse(cookie, &url).unwrap();
+        }
+        let snapshot = before.iter_any().cloned().collect::<Vec<_>>();
+
+        let mut after = before.clone();
+        after.remove("example.com", "/", "removed");
+        after.parse("expired=3; Max-Age=0", &url).unwrap();
+        after
+            .parse("modified=5; HttpOnly; SameSite=Lax; Max-Age=60", &url)
+            .unwrap();
+        after.parse("added=6", &url).unwrap();
+
+        let diff = before.diff(&after);
+        assert_eq!(diff, StoreDiff::new(&snapshot, after.iter_any()));
+        let kinds = diff
+            .entries
+            .iter()
+            .map(|e| (e.name.as_str(), &e.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(4, kinds.len());
+        assert_eq!(("added", &DiffKind::Added), kinds[0]);
+        assert_eq!(("expired", &DiffKind::Expired), kinds[1]);
+        assert_eq!("modified", kinds[2].0);
+        match kinds[2].1 {
+            DiffKind::Modified(ref changes) => {
+                assert_eq!(5, changes.len());
+                assert_eq!(
+                    FieldChange::Value {
+                        old: "4".to_owned(),
+                        new: "5".to_owned(),
+                        sensitive: true
+                    },
+                    changes[0]
+                );
+                assert_eq!(
+                    FieldChange::Secure {
+                        old: true,
+                        new: false
+                    },
+                    changes[1]
+                );
+                assert_eq!(
+                    FieldChange::SameSite {
+                        old: None,
+                        new: Some(SameSite::Lax)
+                    },
+                    changes[3]
+                );
+            }
+            ref kind => panic!("unexpected {kind:?}"),
+        }
+        assert_eq!(("removed", &DiffKind::Removed), kinds[3]);
+        assert_eq!(1, diff.modified().count());
+
+        let rendered = diff.to_string();
+        let lines = rendered.lines().collect::<Vec<_>>();
+        assert_eq!("+ example.com/ added", lines[0]);
+        assert_eq!("x example.com/ expired", lines[1]);
+        let value = if cfg!(feature = "log_secure_cookie_values") {
+            "value \"4\" -> \"5\""
+        } else {
+            "value [redacted] -> [redacted]"
+        };
+        assert!(lines[2].starts_with(&format!(
+            "~ example.com/ modified: {value}, Secure true -> false, HttpOnly false -> true, SameSite unset -> Lax, expires session end -> "
+        )));
+        assert_eq!("- example.com/ removed", lines[3]);
+
+        assert!(after.diff(&after).is_empty());
+    }
+
+    #[test]
+    fn display_plain_value() {
+        let url = test_utils::url("https://example.com/");
+        let mut before = CookieStore::default();
+        before.parse("plain=1", &url).unwrap();
+        let mut after = before.clone();
+        after.parse("plain=2", &url).unwrap();
+        assert_eq!(
+            "~ example.com/ plain: value \"1\" -> \"2\"\n",
+            before.diff(&after).to_string()
+        );
+    }
+}
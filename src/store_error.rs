@@ -0,0 +1,248 @@
+use std::fmt;
+use std::io;
+
+use log::debug;
+
+use crate::cookie::Cookie;
+use crate::utils::is_host_name;
+use crate::IdnaErrors;
+
+/// An error loading or saving a [`CookieStore`](crate::CookieStore)
+///
+/// Loading does not fail on a well-formed entry with no domain, which could never be sent; such
+/// entries are skipped.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum StoreError {
+    /// Reading or writing failed
+    Io(io::Error),
+    /// The JSON input was malformed at `line` and `column` (both 1-based), or JSON
+    /// serialization failed (`line` and `column` are 0). For line-oriented formats, such as that
+    /// of [`CookieStore::load_json`](crate::CookieStore::load_json), `line` is the line of the
+    /// input.
+    #[cfg(feature = "serde_json")]
+    Json {
+        line: usize,
+        column: usize,
+        source: serde_json::Error,
+    },
+    /// The RON input was malformed at `line` and `column` (both 1-based), or RON serialization
+    /// failed (`line` and `column` are 0)
+    #[cfg(feature = "serde_ron")]
+    Ron {
+        line: usize,
+        column: usize,
+        source: ron::Error,
+    },
+    /// The domain of the entry at `index` (0-based; for line-oriented formats, the index of the
+    /// line) is not a valid internationalized domain name
+    Idna { index: usize, source: IdnaErrors },
+    /// Any other error, e.g. from a caller-provided de/serialization function
+    Other(crate::Error),
+}
+
+impl StoreError {
+    /// Attribute a format error to `line` of the input, for line-oriented formats in which each
+    /// line is deserialized separately
+    #[cfg_attr(
+        not(any(feature = "serde_json", feature = "serde_ron")),
+        allow(unused_variables)
+    )]
+    pub(crate) fn at_line(self, line: usize) -> StoreError {
+        match self {
+            #[cfg(feature = "serde_json")]
+            StoreError::Json { column, source, .. } => StoreError::Json {
+                line,
+                column,
+                source,
+            },
+            #[cfg(feature = "serde_ron")]
+            StoreError::Ron { column, source, .. } => StoreError::Ron {
+                line,
+                column,
+                source,
+            },
+            e => e,
+        }
+    }
+}
+
+/// Check that `cookie`, the entry at `index` of loaded input, has a valid domain. An entry with
+/// no domain could never be sent, and is skipped (`Ok(None)`) rather than failing the load.
+pub(crate) fn check_loaded(
+    index: usize,
+    cookie: Cookie<'static>,
+) -> Result<Option<Cookie<'static>>, StoreError> {
+    let domain = match cookie.domain.as_cow() {
+        Some(domain) => domain,
+        None => {
+            debug!(
+                "skipping cookie '{}' at entry {index}: no domain",
+                cookie.name()
+            );
+            return Ok(None);
+        }
+    };
+    // IP addresses are held in their canonical form (IPv6 bracketed), and are not domain names
+    let host = domain
+        .strip_prefix('[')
+        .and_then(|d| d.strip_suffix(']'))
+        .unwrap_or(&domain);
+    if is_host_name(host) {
+        idna::domain_to_ascii(&domain).map_err(|e| StoreError::Idna {
+            index,
+            source: IdnaErrors::from(e),
+        })?;
+    }
+    Ok(Some(cookie))
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            StoreError::Io(_) => write!(f, "I/O error"),
+            #[cfg(feature = "serde_json")]
+            StoreError::Json { line: 0, .. } => write!(f, "JSON serialization failed"),
+            #[cfg(feature = "serde_json")]
+            StoreError::Json { line, column, .. } => {
+                write!(f, "malformed JSON at line {line}, column {column}")
+            }
+            #[cfg(feature = "serde_ron")]
+            StoreError::Ron { line: 0, .. } => write!(f, "RON serialization failed"),
+            #[cfg(feature = "serde_ron")]
+            StoreError::Ron { line, column, .. } => {
+                write!(f, "malformed RON at line {line}, column {column}")
+            }
+            StoreError::Idna { index, .. } => {
+                write!(f, "invalid domain of cookie at entry {index}")
+            }
+            StoreError::Other(ref e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            StoreError::Io(ref e) => Some(e),
+            #[cfg(feature = "serde_json")]
+            StoreError::Json { ref source, .. } => Some(source),
+            #[cfg(feature = "serde_ron")]
+            StoreError::Ron { ref source, .. } => Some(source),
+            StoreError::Idna { ref source, .. } => Some(source),
+            StoreError::Other(ref e) => e.source(),
+        }
+    }
+}
+
+impl From<io::Error> for StoreError {
+    fn from(e: io::Error) -> StoreError {
+        StoreError::Io(e)
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl From<serde_json::Error> for StoreError {
+    fn from(e: serde_json::Error) -> StoreError {
+        if e.is_io() {
+            return StoreError::Io(e.into());
+        }
+        StoreError::Json {
+            line: e.line(),
+            column: e.column(),
+            source: e,
+        }
+    }
+}
+
+#[cfg(feature = "serde_ron")]
+impl From<ron::error::SpannedError> for StoreError {
+    fn from(e: ron::error::SpannedError) -> StoreError {
+        StoreError::Ron {
+            line: e.position.line,
+            column: e.position.col,
+            source: e.code,
+        }
+    }
+}
+
+#[cfg(feature = "serde_ron")]
+impl From<ron::Error> for StoreError {
+    fn from(e: ron::Error) -> StoreError {
+        StoreError::Ron {
+            line: 0,
+            column: 0,
+            source: e,
+        }
+    }
+}
+
+impl From<crate::Error> for StoreError {
+    fn from(e: crate::Error) -> StoreError {
+        StoreError::Other(e)
+    }
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests {
+    use std::error::Error as _;
+
+    use super::StoreError;
+
+    fn entry(domain: &str) -> String {
+        format!(
+            r#"{{"raw_cookie":"a=1","path":["/",false],"domain":{domain},"expires":{{"AtUtc":"3000-01-01T00:00:00Z"}}}}"#
+        )
+    }
+
+    #[test]
+    fn positions() {
+        let input = format!(
+            "[\n  {},\n  {{\"raw_cookie\": 1}}\n]",
+            entry(r#"{"HostOnly":"a.com"}"#)
+        );
+        match crate::serde::json::load(input.as_bytes()) {
+            Err(StoreError::Json { line, source, .. }) => {
+                assert_eq!(3, line);
+                assert!(source.is_data());
+            }
+            r => panic!("unexpected {r:?}"),
+        }
+
+        #[allow(deprecated)]
+        let legacy = crate::CookieStore::load_json(
+            format!("{}\n{{\n", entry(r#"{"HostOnly":"a.com"}"#)).as_bytes(),
+        );
+        match legacy {
+            Err(StoreError::Json { line, column, .. }) => assert_eq!((2, 1), (line, column)),
+            r => panic!("unexpected {r:?}"),
+        }
+
+        let e = crate::serde::json::load(&b"[1"[..]).unwrap_err();
+        assert_eq!("malformed JSON at line 1, column 2", e.to_string());
+        assert!(e.source().is_some());
+    }
+
+    #[test]
+    fn invalid_cookies() {
+        let input = format!(
+            "[{},{}]",
+            entry(r#"{"HostOnly":"[::1]"}"#),
+            entry(r#""NotPresent""#)
+        );
+        let store = crate::serde::json::load_all(input.as_bytes()).unwrap();
+        assert_eq!(1, store.iter_any().count());
+        assert!(store.contains("[::1]", "/", "a"));
+
+        let input = format!("[{}]", entry(r#"{"Suffix":"xn--a.com"}"#));
+        match crate::serde::json::load_all(input.as_bytes()) {
+            Err(StoreError::Idna { index: 0, .. }) => {}
+            r => panic!("unexpected {r:?}"),
+        }
+
+        match crate::serde::json::load_from_path("/nonexistent/cookies.json") {
+            Err(StoreError::Io(e)) => assert_eq!(std::io::ErrorKind::NotFound, e.kind()),
+            r => panic!("unexpected {r:?}"),
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use std::fmt;
+
+use time::OffsetDateTime;
+
+use crate::cookie::Cookie;
+use crate::cookie_expiration::CookieExpiration;
+
+/// Which of two conflicting cookies a merge retains
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub enum MergeChoice {
+    /// Retain the `Cookie` already in the store
+    Existing,
+    /// Replace the `Cookie` already in the store with the incoming `Cookie`
+    Incoming,
+}
+
+type Resolver = Box<dyn Fn(&Cookie<'static>, &Cookie<'static>) -> MergeChoice + Send + Sync>;
+
+/// How [`CookieStore::merge`](crate::CookieStore::merge) resolves a conflict, i.e. an incoming
+/// `Cookie` with the same domain, path, and name as an __unexpired__ `Cookie` already in the
+/// store. On a tie, the existing `Cookie` is retained.
+pub enum MergeStrategy {
+    /// Retain whichever `Cookie` was created most recently, to the second (as creation times
+    /// are persisted); a `Cookie` without a [`creation_time`](crate::Cookie::creation_time) is
+    /// considered the oldest
+    NewestCreation,
+    /// Retain whichever `Cookie` expires last; a __non-persistent__ `Cookie` is considered to
+    /// expire before any __persistent__ `Cookie`
+    LatestExpiry,
+    /// Always retain the existing `Cookie`
+    KeepExisting,
+    /// Decide with a closure, called with the existing and incoming cookies
+    Custom(Resolver),
+}
+
+impl MergeStrategy {
+    pub(crate) fn resolve(
+        &self,
+        existing: &Cookie<'static>,
+        incoming: &Cookie<'static>,
+    ) -> MergeChoice {
+        let incoming_wins = match *self {
+            MergeStrategy::NewestCreation => {
+                incoming.creation_time().map(OffsetDateTime::unix_timestamp)
+                    > existing.creation_time().map(OffsetDateTime::unix_timestamp)
+            }
+            MergeStrategy::LatestExpiry => match (&existing.expires, &incoming.expires) {
+                (CookieExpiration::AtUtc(existing), CookieExpiration::AtUtc(incoming)) => {
+                    incoming > existing
+                }
+                (CookieExpiration::SessionEnd, CookieExpiration::AtUtc(_)) => true,
+                (_, CookieExpiration::SessionEnd) => false,
+            },
+            MergeStrategy::KeepExisting => false,
+            MergeStrategy::Custom(ref choose) => return choose(existing, incoming),
+        };
+        if incoming_wins {
+            MergeChoice::Incoming
+        } else {
+            MergeChoice::Existing
+        }
+    }
+}
+
+impl fmt::Debug for MergeStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            MergeStrategy::NewestCreation => f.write_str("NewestCreation"),
+            MergeStrategy::LatestExpiry => f.write_str("LatestExpiry"),
+            MergeStrategy::KeepExisting => f.write_str("KeepExisting"),
+            MergeStrategy::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+/// A conflict encountered while merging, and how it was resolved
+#[derive(PartialEq, Clone, Debug, Eq)]
+pub struct MergeConflict {
+    pub domain: String,
+    pub path: String,
+    pub name: String,
+    pub resolution: MergeChoice,
+}
+
+/// The outcome of a [`CookieStore::merge`](crate::CookieStore::merge)
+#[derive(PartialEq, Clone, Debug, Default, Eq)]
+pub struct MergeReport {
+    /// The number of incoming cookies which did not conflict with an existing `Cookie`, and were
+    /// added to the store
+    pub added: usize,
+    /// The number of incoming cookies which were __expired__, and so skipped
+    pub skipped_expired: usize,
+    /// The conflicts encountered, in the order the incoming cookies were merged
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl MergeReport {
+    /// The number of conflicts resolved in favor of the incoming `Cookie`
+    pub fn replaced(&self) -> usize {
+        self.conflicts
+            .iter()
+            .filter(|c| c.resolution == MergeChoice::Incoming)
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::Duration;
+
+    use super::{MergeChoice, MergeConflict, MergeStrategy};
+    use crate::utils::test as test_utils;
+    use crate::{CookieStore, ManualClock};
+
+    fn stores(clock: &ManualClock) -> (CookieStore, CookieStore) {
+        let url = test_utils::url("https://example.com/");
+        let mut a = CookieStore::default().with_clock(clock.clone());
+        let mut b = CookieStore::default().with_clock(clock.clone());
+        a.parse("shared=a; Max-Age=7200", &url).unwrap();
+        a.parse("only_a=a", &url).unwrap();
+        clock.advance(Duration::minutes(1));
+        b.parse("shared=b; Max-Age=3600", &url).unwrap();
+        b.parse("only_b=b", &url).unwrap();
+        b.parse("expired=b; Max-Age=60", &url).unwrap();
+        clock.advance(Duration::minutes(1));
+        (a, b)
+    }
+
+    fn shared(store: &CookieStore) -> &str {
+        store.get("example.com", "/", "shared").unwrap().value()
+    }
+
+    #[test]
+    fn strategies() {
+        for (strategy, exp) in [
+            (MergeStrategy::NewestCreation, "b"),
+            (MergeStrategy::LatestExpiry, "a"),
+            (MergeStrategy::KeepExisting, "a"),
+            (
+                MergeStrategy::Custom(Box::new(|existing, incoming| {
+                    if incoming.value() > existing.value() {
+                        MergeChoice::Incoming
+                    } else {
+                        MergeChoice::Existing
+                    }
+                })),
+                "b",
+            ),
+        ] {
+            let clock = ManualClock::new(time::OffsetDateTime::UNIX_EPOCH);
+            let (mut a, b) = stores(&clock);
+            let report = a.merge(b, &strategy);
+            assert_eq!(1, report.added, "{strategy:?}");
+            assert_eq!(1, report.skipped_expired, "{strategy:?}");
+            let resolution = if exp == "b" {
+                MergeChoice::Incoming
+            } else {
+                MergeChoice::Existing
+            };
+            assert_eq!(
+                vec![MergeConflict {
+                    domain: "example.com".to_owned(),
+                    path: "/".to_owned(),
+                    name: "shared".to_owned(),
+                    resolution,
+                }],
+                report.conflicts,
+                "{strategy:?}"
+            );
+            assert_eq!(exp, shared(&a), "{strategy:?}");
+            assert!(a.contains("example.com", "/", "only_b"));
+            assert!(!a.contains_any("example.com", "/", "expired"));
+            assert_eq!(3, a.iter_any().count());
+        }
+    }
+
+    #[test]
+    fn newest_creation_to_the_second() {
+        let url = test_utils::url("https://example.com/");
+        let clock = ManualClock::new(time::OffsetDateTime::UNIX_EPOCH);
+        let mut a = CookieStore::default().with_clock(clock.clone());
+        a.parse("shared=a", &url).unwrap();
+        clock.advance(Duration::milliseconds(500));
+        let mut b = CookieStore::default().with_clock(clock.clone());
+        b.parse("shared=b", &url).unwrap();
+        a.merge(b, &MergeStrategy::NewestCreation);
+        assert_eq!("a", shared(&a));
+    }
+
+    #[test]
+    fn send_sync() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+        assert_send_sync(&MergeStrategy::Custom(Box::new(|_, _| {
+            MergeChoice::Existing
+        })));
+    }
+}
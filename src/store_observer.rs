@@ -0,0 +1,192 @@
+use std::fmt;
+use std::sync::Arc;
+
+use url::Url;
+
+use crate::cookie::Cookie;
+
+/// The cause of a change to a [`CookieStore`](crate::CookieStore)
+#[derive(PartialEq, Clone, Copy, Debug, Eq)]
+pub enum ChangeCause {
+    /// A `Cookie` was added to the store
+    Inserted,
+    /// A `Cookie` was added to the store, replacing an existing entry
+    UpdatedExisting,
+    /// An __expired__ `Cookie` expired a `Cookie` already in the store
+    ExpiredExisting,
+    /// A `Cookie` in the store was expired in place
+    Expired,
+    /// A `Cookie` was removed from the store
+    Removed,
+    /// A `Cookie` was removed from the store to make room for another, per
+    /// [`CookieStore::with_max_cookies_per_domain`](crate::CookieStore::with_max_cookies_per_domain)
+    Evicted,
+    /// A `Cookie` was removed from the store as it was cleared
+    Cleared,
+    /// A change made within a [`Transaction`](crate::Transaction) was undone as it was rolled
+    /// back
+    RolledBack,
+}
+
+/// A single change to a [`CookieStore`](crate::CookieStore), as delivered to observers
+/// registered via [`CookieStore::add_observer`](crate::CookieStore::add_observer)
+#[derive(Clone, Copy, Debug)]
+pub struct StoreChange<'a> {
+    /// What caused the change
+    pub cause: ChangeCause,
+    /// The request-uri the change was received from, if it resulted from storing a `Cookie`
+    pub request_url: Option<&'a Url>,
+    /// The `Cookie` as it was prior to the change, if it was in the store
+    pub old: Option<&'a Cookie<'static>>,
+    /// The `Cookie` as it is after the change, if it remains in the store
+    pub new: Option<&'a Cookie<'static>>,
+}
+
+/// Identifies an observer registered with a [`CookieStore`](crate::CookieStore), for later
+/// removal
+#[derive(PartialEq, Clone, Copy, Debug, Eq, Hash)]
+pub struct ObserverId(u64);
+
+type Observer = Arc<dyn Fn(&StoreChange<'_>) + Send + Sync>;
+
+/// The observers registered with a `CookieStore`. A clone holds the same observers, but is
+/// registered with and unregistered from independently.
+#[derive(Clone, Default)]
+pub(crate) struct Observers {
+    next_id: u64,
+    observers: Vec<(ObserverId, Observer)>,
+}
+
+impl Observers {
+    pub(crate) fn add(&mut self, observer: Observer) -> ObserverId {
+        let id = ObserverId(self.next_id);
+        self.next_id += 1;
+        self.observers.push((id, observer));
+        id
+    }
+
+    pub(crate) fn remove(&mut self, id: ObserverId) -> bool {
+        let len = self.observers.len();
+        self.observers.retain(|(observer_id, _)| *observer_id != id);
+        len != self.observers.len()
+    }
+
+    /// Returns true if no observers are registered, in which case callers may skip preparing a
+    /// `StoreChange`
+    pub(crate) fn is_empty(&self) -> bool {
+        self.observers.is_empty()
+    }
+
+    pub(crate) fn notify(&self, change: StoreChange<'_>) {
+        for (_, observer) in &self.observers {
+            observer(&change);
+        }
+    }
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.observers.iter().map(|(id, _)| id))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::ChangeCause;
+    use crate::utils::test as test_utils;
+    use crate::CookieStore;
+
+    type Log = Arc<Mutex<Vec<(ChangeCause, Option<String>, Option<String>, Option<String>)>>>;
+
+    fn observed_store() -> (CookieStore, Log) {
+        let mut store = CookieStore::default();
+        let log = Log::default();
+        let observer_log = log.clone();
+        store.add_observer(move |change| {
+            observer_log.lock().unwrap().push((
+                change.cause,
+                change.request_url.map(|u| u.to_string()),
+                change.old.map(|c| c.value().to_owned()),
+                change.new.map(|c| c.value().to_owned()),
+            ))
+        });
+        (store, log)
+    }
+
+    fn s(s: &str) -> Option<String> {
+        Some(s.to_owned())
+    }
+
+    #[test]
+    fn changes() {
+        let (mut store, log) = observed_store();
+        let url = test_utils::url("http://example.com/foo/bar");
+        store.parse("cookie1=value1", &url).unwrap();
+        store.parse("cookie1=value2", &url).unwrap();
+        store.store_response_cookies(
+            vec![
+                ::cookie::Cookie::parse("cookie2=value3").unwrap(),
+                // rejected, so not observed
+                ::cookie::Cookie::parse("cookie3=value4; Domain=example.org").unwrap(),
+            ]
+            .into_iter(),
+            &url,
+        );
+        store.parse("cookie1=value2; Max-Age=0", &url).unwrap();
+        store.remove("example.com", "/foo", "cookie1");
+        store.remove("example.com", "/foo", "cookie1");
+        store.clear();
+
+        let url = s("http://example.com/foo/bar");
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                (ChangeCause::Inserted, url.clone(), None, s("value1")),
+                (
+                    ChangeCause::UpdatedExisting,
+                    url.clone(),
+                    s("value1"),
+                    s("value2")
+                ),
+                (ChangeCause::Inserted, url.clone(), None, s("value3")),
+                (ChangeCause::ExpiredExisting, url, s("value2"), s("value2")),
+                (ChangeCause::Removed, None, s("value2"), None),
+                (ChangeCause::Cleared, None, s("value3"), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn remove_observer() {
+        let (mut store, log) = observed_store();
+        let url = test_utils::url("http://example.com/foo/bar");
+        let count = Arc::new(Mutex::new(0));
+        let observer_count = count.clone();
+        let id = store.add_observer(move |_| *observer_count.lock().unwrap() += 1);
+        store.parse("cookie1=value1", &url).unwrap();
+        assert!(store.remove_observer(id));
+        assert!(!store.remove_observer(id));
+        store.parse("cookie2=value2", &url).unwrap();
+        assert_eq!(1, *count.lock().unwrap());
+        assert_eq!(2, log.lock().unwrap().len());
+    }
+
+    #[test]
+    fn clone() {
+        let (mut store, log) = observed_store();
+        let url = test_utils::url("http://example.com/foo/bar");
+        let mut clone = store.clone();
+        let count = Arc::new(Mutex::new(0));
+        let observer_count = count.clone();
+        clone.add_observer(move |_| *observer_count.lock().unwrap() += 1);
+        // the observer registered before cloning is notified of changes to either store
+        store.parse("cookie1=value1", &url).unwrap();
+        clone.parse("cookie1=value1", &url).unwrap();
+        assert_eq!(2, log.lock().unwrap().len());
+        assert_eq!(1, *count.lock().unwrap());
+    }
+}
@@ -0,0 +1,338 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use cookie::Cookie as RawCookie;
+use log::debug;
+use url::Url;
+
+use crate::cookie::Cookie;
+use crate::cookie_store::InsertResult;
+use crate::CookieStore;
+
+/// Removed entries of the base store, by domain, path, then name
+type Tombstones = HashMap<String, HashMap<String, HashSet<String>>>;
+
+/// A writable layer over a shared, read-only base [`CookieStore`], e.g. a jar of single sign-on
+/// cookies shared by several workers, each with its own overlay.
+///
+/// Cookies stored in the overlay shadow those of the base with the same domain, path, and
+/// name; cookies removed from (or expired in) the overlay are hidden from the base. The base
+/// itself is never modified. The overlay takes the configuration (public suffix list, policy,
+/// third-party blocking, and clock) of the base.
+///
+/// ```
+/// # use std::sync::Arc;
+/// # use cookie_store::{CookieStore, OverlayCookieStore};
+/// let url = url::Url::parse("https://example.com/").unwrap();
+/// let mut base = CookieStore::default();
+/// base.parse("sso=base", &url).unwrap();
+/// base.parse("theme=dark", &url).unwrap();
+/// let mut overlay = OverlayCookieStore::new(Arc::new(base));
+/// overlay.parse("sso=local", &url).unwrap();
+/// overlay.remove("example.com", "/", "theme");
+/// assert_eq!("local", overlay.get("example.com", "/", "sso").unwrap().value());
+/// assert!(overlay.get("example.com", "/", "theme").is_none());
+/// assert_eq!("base", overlay.base().get("example.com", "/", "sso").unwrap().value());
+/// ```
+#[derive(Debug, Clone)]
+pub struct OverlayCookieStore {
+    base: Arc<CookieStore>,
+    overlay: CookieStore,
+    tombstones: Tombstones,
+}
+
+impl OverlayCookieStore {
+    /// An empty overlay over `base`
+    pub fn new(base: Arc<CookieStore>) -> OverlayCookieStore {
+        OverlayCookieStore {
+            overlay: base.empty(),
+            base,
+            tombstones: Tombstones::new(),
+        }
+    }
+
+    /// The shared base store
+    pub fn base(&self) -> &Arc<CookieStore> {
+        &self.base
+    }
+
+    /// The cookies stored in the overlay, excluding those of the base
+    pub fn overlay(&self) -> &CookieStore {
+        &self.overlay
+    }
+
+    fn is_tombstoned(&self, domain: &str, path: &str, name: &str) -> bool {
+        self.tombstones
+            .get(domain)
+            .and_then(|paths| paths.get(path))
+            .map_or(false, |names| names.contains(name))
+    }
+
+    /// Returns true if the entry of the base store for `domain`, `path`, and `name` is shadowed
+    /// by the overlay or has been removed
+    fn is_hidden(&self, domain: &str, path: &str, name: &str) -> bool {
+        self.overlay.contains_any(domain, path, name) || self.is_tombstoned(domain, path, name)
+    }
+
+    fn is_hidden_cookie(&self, cookie: &Cookie<'_>) -> bool {
+        let domain = cookie.domain.as_cow();
+        self.is_hidden(
+            domain.as_deref().unwrap_or_default(),
+            &cookie.path,
+            cookie.name(),
+        )
+    }
+
+    /// The entry of the base store for `domain`, `path`, and `name`, unless hidden
+    fn visible_base(&self, domain: &str, path: &str, name: &str) -> Option<&Cookie<'static>> {
+        if self.is_hidden(domain, path, name) {
+            None
+        } else {
+            self.base.get_any(domain, path, name)
+        }
+    }
+
+    /// Returns true if an __unexpired__ `Cookie` corresponding to the specified `domain`,
+    /// `path`, and `name` is visible through the overlay
+    pub fn contains(&self, domain: &str, path: &str, name: &str) -> bool {
+        self.get(domain, path, name).is_some()
+    }
+
+    /// Returns a reference to the __unexpired__ `Cookie` corresponding to the specified
+    /// `domain`, `path`, and `name`, from the overlay if present there, otherwise from the base
+    pub fn get(&self, domain: &str, path: &str, name: &str) -> Option<&Cookie<'_>> {
+        if self.overlay.contains_any(domain, path, name) {
+            self.overlay.get(domain, path, name)
+        } else if self.is_tombstoned(domain, path, name) {
+            None
+        } else {
+            self.base.get(domain, path, name)
+        }
+    }
+
+    /// Returns a collection of references to __unexpired__ cookies that match `request_url`, as
+    /// for [`CookieStore::matches`], those of the overlay first
+    pub fn matches(&self, request_url: &Url) -> Vec<&Cookie<'static>> {
+        let mut matches = self.overlay.matches(request_url);
+        matches.extend(
+            self.base
+                .matches(request_url)
+                .into_iter()
+                .filter(|c| !self.is_hidden_cookie(c)),
+        );
+        matches
+    }
+
+    /// Return an `Iterator` of the cookie (`name`, `value`) pairs for `url`, suitable for use in
+    /// the `Cookie` header of an HTTP request; see [`CookieStore::get_request_values`]
+    pub fn get_request_values(&self, url: &Url) -> impl Iterator<Item = (&str, &str)> {
+        self.matches(url).into_iter().map(|c| c.name_value())
+    }
+
+    /// An iterator visiting all the __unexpired__ cookies visible through the overlay, those of
+    /// the overlay first
+    pub fn iter_unexpired<'a>(&'a self) -> impl Iterator<Item = &'a Cookie<'static>> + 'a {
+        self.overlay.iter_unexpired().chain(
+            self.base
+                .iter_unexpired()
+                .filter(move |c| !self.is_hidden_cookie(c)),
+        )
+    }
+
+    /// Parses a new `Cookie` from `cookie_str` and inserts it into the overlay
+    pub fn parse(&mut self, cookie_str: &str, request_url: &Url) -> InsertResult {
+        Cookie::parse_at(cookie_str, request_url, self.overlay.now())
+            .and_then(|cookie| self.insert(cookie.into_owned(), request_url))
+    }
+
+    /// Store the `cookies` received from `url` in the overlay
+    pub fn store_response_cookies<I: Iterator<Item = RawCookie<'static>>>(
+        &mut self,
+        cookies: I,
+        url: &Url,
+    ) {
+        for cookie in cookies {
+            if let Err(e) = Cookie::try_from_raw_cookie_at(&cookie, url, self.overlay.now())
+                .and_then(|cookie| self.insert(cookie.into_owned(), url))
+            {
+                debug!("unable to store Set-Cookie: {e:?}");
+            }
+        }
+    }
+
+    /// Inserts `cookie`, received from `request_url`, into the overlay, per the rules of
+    /// [`CookieStore::insert`]. A visible `Cookie` of the base with the same domain, path, and
+    /// name is treated as the existing entry: it may not be replaced by a non-HTTP API if it is
+    /// HttpOnly, its creation time is retained, and an __expired__ `cookie` hides it.
+    pub fn insert(&mut self, cookie: Cookie<'static>, request_url: &Url) -> InsertResult {
+        let domain = String::from(&cookie.domain);
+        let path = String::from(&cookie.path);
+        let name = cookie.name().to_owned();
+        // copy the entry of the base up into the overlay, so that the overlay handles it as the
+        // old-cookie; if the insert fails, the copy is discarded
+        let copied = match self.visible_base(&domain, &path, &name) {
+            Some(base_cookie) => {
+                let base_cookie = base_cookie.clone();
+                self.overlay.store_cookie(base_cookie);
+                true
+            }
+            None => false,
+        };
+        let result = self.overlay.insert(cookie, request_url);
+        if result.is_err() && copied {
+            self.overlay.remove(&domain, &path, &name);
+        }
+        result
+    }
+
+    /// Removes a `Cookie` from the overlay, and hides any `Cookie` of the base with the same
+    /// domain, path, and name. Returns the `Cookie` which was visible, if any.
+    pub fn remove(&mut self, domain: &str, path: &str, name: &str) -> Option<Cookie<'static>> {
+        let base = self.visible_base(domain, path, name).cloned();
+        let removed = self.overlay.remove(domain, path, name);
+        if self.base.contains_any(domain, path, name) {
+            self.tombstones
+                .entry(domain.to_owned())
+                .or_default()
+                .entry(path.to_owned())
+                .or_default()
+                .insert(name.to_owned());
+        }
+        removed.or(base)
+    }
+
+    /// Discard the contents of the overlay, including removals, so that the base is visible
+    pub fn reset(&mut self) {
+        self.overlay.clear();
+        self.tombstones.clear();
+    }
+
+    /// Flatten the overlay and the base into a single `CookieStore`, with the configuration of
+    /// the base
+    pub fn flatten(&self) -> CookieStore {
+        let mut store = CookieStore::clone(&self.base).with_config_of(&self.base);
+        for (domain, paths) in &self.tombstones {
+            for (path, names) in paths {
+                for name in names {
+                    store.remove(domain, path, name);
+                }
+            }
+        }
+        for cookie in self.overlay.iter_any() {
+            store.store_cookie(cookie.clone());
+        }
+        store
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::OverlayCookieStore;
+    use crate::utils::test as test_utils;
+    use crate::{CookieErrorKind, CookieStore, ManualClock, StoreAction};
+
+    fn base() -> Arc<CookieStore> {
+        Arc::new(test_utils::store(&[
+            ("sso=base; HttpOnly", "https://example.com/"),
+            ("theme=dark", "https://example.com/"),
+            ("lang=en", "https://example.com/"),
+        ]))
+    }
+
+    #[test]
+    fn overlay() {
+        let url = test_utils::url("https://example.com/");
+        let base = base();
+        let mut overlay = OverlayCookieStore::new(base.clone());
+        assert_eq!(
+            vec!["lang=en", "sso=base", "theme=dark"],
+            test_utils::values(overlay.matches(&url))
+        );
+
+        assert_eq!(
+            Ok(StoreAction::UpdatedExisting),
+            overlay.parse("sso=local; HttpOnly", &url)
+        );
+        assert_eq!(
+            Ok(StoreAction::ExpiredExisting),
+            overlay.parse("theme=dark; Max-Age=0", &url)
+        );
+        assert_eq!(Ok(StoreAction::Inserted), overlay.parse("worker=1", &url));
+        assert_eq!(
+            "en",
+            overlay.remove("example.com", "/", "lang").unwrap().value()
+        );
+        assert!(overlay.remove("example.com", "/", "lang").is_none());
+
+        // HttpOnly base cookies are protected from non-HTTP APIs
+        let mut protected = OverlayCookieStore::new(base.clone());
+        assert_eq!(
+            Err(CookieErrorKind::NonHttpScheme),
+            protected
+                .insert(
+                    test_utils::make_cookie("sso=script", "https://example.com/", None, None),
+                    &test_utils::url("ftp://example.com/")
+                )
+                .map_err(|e| e.kind())
+        );
+        assert!(!protected.overlay().contains_any("example.com", "/", "sso"));
+
+        assert_eq!(
+            vec!["sso=local", "worker=1"],
+            test_utils::values(overlay.matches(&url))
+        );
+        assert_eq!(
+            vec!["sso=local", "worker=1"],
+            test_utils::values(overlay.iter_unexpired())
+        );
+        assert!(overlay.get("example.com", "/", "theme").is_none());
+        assert!(!overlay.contains("example.com", "/", "lang"));
+
+        // the base is untouched
+        assert_eq!(3, base.iter_unexpired().count());
+        assert_eq!("base", base.get("example.com", "/", "sso").unwrap().value());
+
+        let flat = overlay.flatten();
+        assert_eq!(
+            vec!["sso=local", "worker=1"],
+            test_utils::values(flat.iter_unexpired())
+        );
+        assert!(!flat.contains_any("example.com", "/", "lang"));
+
+        // removed base cookies may be stored again
+        overlay.parse("lang=fr", &url).unwrap();
+        assert_eq!(
+            "fr",
+            overlay.get("example.com", "/", "lang").unwrap().value()
+        );
+
+        overlay.reset();
+        assert_eq!(
+            vec!["lang=en", "sso=base", "theme=dark"],
+            test_utils::values(overlay.matches(&url))
+        );
+    }
+
+    #[test]
+    fn clock() {
+        let url = test_utils::url("https://example.com/");
+        let clock = ManualClock::new(time::OffsetDateTime::UNIX_EPOCH);
+        let base = Arc::new(CookieStore::default().with_clock(clock.clone()));
+        let mut overlay = OverlayCookieStore::new(base);
+        overlay.parse("parsed=1; Max-Age=60", &url).unwrap();
+        overlay.store_response_cookies(
+            vec![cookie::Cookie::parse("stored=2; Max-Age=120").unwrap()].into_iter(),
+            &url,
+        );
+        assert_eq!(
+            vec!["parsed=1", "stored=2"],
+            test_utils::values(overlay.matches(&url))
+        );
+        clock.advance(time::Duration::seconds(90));
+        assert_eq!(vec!["stored=2"], test_utils::values(overlay.matches(&url)));
+        clock.advance(time::Duration::seconds(60));
+        assert!(overlay.matches(&url).is_empty());
+    }
+}
@@ -0,0 +1,156 @@
+use std::fmt;
+
+use cookie::Cookie as RawCookie;
+
+use crate::cookie::Error as CookieError;
+use crate::cookie_store::{InsertResult, StoreAction};
+
+/// The outcome of storing a single Set-Cookie. Unless the `log_secure_cookie_values` feature
+/// is enabled, the `Debug` output omits the value of a Secure or HttpOnly cookie.
+#[derive(PartialEq, Clone)]
+pub struct SetCookieOutcome {
+    /// The cookie as received
+    pub cookie: RawCookie<'static>,
+    /// How the cookie was stored, or why it was rejected
+    pub result: InsertResult,
+}
+
+impl fmt::Debug for SetCookieOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let is_sensitive =
+            self.cookie.secure() == Some(true) || self.cookie.http_only() == Some(true);
+        let mut cookie = self.cookie.clone();
+        if is_sensitive && !cfg!(feature = "log_secure_cookie_values") {
+            cookie.set_value("[redacted]");
+        }
+        f.debug_struct("SetCookieOutcome")
+            .field("cookie", &format_args!("{cookie}"))
+            .field("result", &self.result)
+            .finish()
+    }
+}
+
+/// The outcome of
+/// [`CookieStore::store_response_cookies_with_report`](crate::CookieStore::store_response_cookies_with_report),
+/// with an entry for each Set-Cookie in the order received
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct SetCookieReport {
+    pub outcomes: Vec<SetCookieOutcome>,
+}
+
+impl SetCookieReport {
+    /// The cookies which were stored, and how
+    pub fn stored(&self) -> impl Iterator<Item = (&RawCookie<'static>, &StoreAction)> {
+        self.outcomes
+            .iter()
+            .filter_map(|o| o.result.as_ref().ok().map(|action| (&o.cookie, action)))
+    }
+
+    /// The cookies which were rejected, and why
+    pub fn rejected(&self) -> impl Iterator<Item = (&RawCookie<'static>, &CookieError)> {
+        self.outcomes
+            .iter()
+            .filter_map(|o| o.result.as_ref().err().map(|e| (&o.cookie, e)))
+    }
+
+    /// Returns true if every cookie was stored
+    pub fn all_stored(&self) -> bool {
+        self.outcomes.iter().all(|o| o.result.is_ok())
+    }
+
+    /// The outcome for the cookie named `name`; if several were received, the last
+    pub fn get(&self, name: &str) -> Option<&SetCookieOutcome> {
+        self.outcomes.iter().rev().find(|o| o.cookie.name() == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::test as test_utils;
+    use crate::{CookieErrorKind, CookieStore, RawCookie, StoreAction};
+
+    fn set_cookies(cookies: &[&str]) -> impl Iterator<Item = RawCookie<'static>> {
+        cookies
+            .iter()
+            .map(|c| RawCookie::parse(c.to_string()).unwrap())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn report() {
+        let url = test_utils::url("https://www.example.com/");
+        let mut store = CookieStore::default();
+        store.parse("old=1", &url).unwrap();
+        let report = store.store_response_cookies_with_report(
+            set_cookies(&[
+                "session=1; Domain=example.com",
+                "other=1; Domain=other.com",
+                "old=2",
+                "gone=1; Max-Age=0",
+            ]),
+            &url,
+        );
+        assert_eq!(4, report.outcomes.len());
+        assert!(!report.all_stored());
+        assert_eq!(
+            vec![
+                ("session", &StoreAction::Inserted),
+                ("old", &StoreAction::UpdatedExisting)
+            ],
+            report
+                .stored()
+                .map(|(c, action)| (c.name(), action))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![
+                ("other", CookieErrorKind::DomainMismatch),
+                ("gone", CookieErrorKind::Expired)
+            ],
+            report
+                .rejected()
+                .map(|(c, e)| (c.name(), e.kind()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "other.com",
+            report.get("other").unwrap().cookie.domain().unwrap()
+        );
+        let gone = report.get("gone").unwrap().result.as_ref().unwrap_err();
+        assert_eq!(Some("www.example.com"), gone.domain());
+        assert_eq!(Some("www.example.com"), gone.request_host());
+        assert!(report.get("missing").is_none());
+        assert!(store.contains("example.com", "/", "session"));
+        assert!(!store.contains_any("other.com", "/", "other"));
+
+        let report = store.store_response_cookies_for_site_with_report(
+            set_cookies(&["tracker=1"]),
+            &url,
+            &test_utils::url("https://www.example.com/page"),
+        );
+        assert!(report.all_stored());
+    }
+
+    #[test]
+    fn debug_redacts_sensitive_values() {
+        let url = test_utils::url("https://www.example.com/");
+        let mut store = CookieStore::default();
+        let report = store.store_response_cookies_with_report(
+            set_cookies(&[
+                "plain=visible",
+                "secure=secret1; Secure",
+                "http_only=secret2; HttpOnly",
+            ]),
+            &url,
+        );
+        let debug = format!("{report:?}");
+        assert!(debug.contains("plain=visible"), "{debug}");
+        assert_eq!(
+            cfg!(feature = "log_secure_cookie_values"),
+            debug.contains("secret1") || debug.contains("secret2"),
+            "{debug}"
+        );
+        assert_eq!("secret1", report.get("secure").unwrap().cookie.value());
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::BTreeMap;
+
+use cookie::SameSite;
+use time::OffsetDateTime;
+
+use crate::cookie::Cookie;
+
+/// The number of cookies with each `SameSite` attribute value
+#[derive(PartialEq, Clone, Copy, Debug, Default, Eq)]
+pub struct SameSiteCounts {
+    pub strict: usize,
+    pub lax: usize,
+    pub none: usize,
+    /// Cookies without a `SameSite` attribute
+    pub unset: usize,
+}
+
+/// A summary of the contents of a [`CookieStore`](crate::CookieStore), as returned by
+/// [`CookieStore::stats`](crate::CookieStore::stats). Other than `expired`, all counts are of
+/// __unexpired__ cookies.
+#[derive(PartialEq, Clone, Debug, Default, Eq)]
+pub struct StoreStats {
+    /// The number of __unexpired__ __non-persistent__ cookies
+    pub session: usize,
+    /// The number of __unexpired__ __persistent__ cookies
+    pub persistent: usize,
+    /// The number of __expired__ cookies retained in the store
+    pub expired: usize,
+    /// The total length in bytes of the names and values of the cookies
+    pub total_bytes: usize,
+    /// The number of cookies with the `Secure` attribute
+    pub secure: usize,
+    /// The number of cookies with the `HttpOnly` attribute
+    pub http_only: usize,
+    pub same_site: SameSiteCounts,
+    /// The number of cookies by domain
+    pub per_domain: BTreeMap<String, usize>,
+    /// The number of cookies by registrable domain, if the store has a public suffix list
+    pub per_registrable_domain: Option<BTreeMap<String, usize>>,
+    /// The earliest expiration time of the __persistent__ cookies, if any
+    pub next_expiration: Option<OffsetDateTime>,
+}
+
+impl StoreStats {
+    /// The number of __unexpired__ cookies
+    pub fn unexpired(&self) -> usize {
+        self.session + self.persistent
+    }
+
+    /// Account for `cookie` as of `now`, under `registrable_domain` if given and
+    /// `per_registrable_domain` is set
+    pub(crate) fn record(
+        &mut self,
+        cookie: &Cookie<'_>,
+        now: &OffsetDateTime,
+        registrable_domain: Option<&str>,
+    ) {
+        if cookie.expires_by(now) {
+            self.expired += 1;
+            return;
+        }
+        if cookie.is_persistent() {
+            self.persistent += 1;
+        } else {
+            self.session += 1;
+        }
+        self.total_bytes += cookie.name().len() + cookie.value().len();
+        if cookie.secure().unwrap_or(false) {
+            self.secure += 1;
+        }
+        if cookie.http_only().unwrap_or(false) {
+            self.http_only += 1;
+        }
+        match cookie.same_site() {
+            Some(SameSite::Strict) => self.same_site.strict += 1,
+            Some(SameSite::Lax) => self.same_site.lax += 1,
+            Some(SameSite::None) => self.same_site.none += 1,
+            None => self.same_site.unset += 1,
+        }
+        if let (Some(per_registrable_domain), Some(registrable_domain)) =
+            (self.per_registrable_domain.as_mut(), registrable_domain)
+        {
+            count(per_registrable_domain, registrable_domain);
+        }
+        count(
+            &mut self.per_domain,
+            &cookie.domain.as_cow().unwrap_or_default(),
+        );
+    }
+}
+
+/// Increment the count of `key`, allocating only on its first occurrence
+fn count(counts: &mut BTreeMap<String, usize>, key: &str) {
+    match counts.get_mut(key) {
+        Some(n) => *n += 1,
+        None => {
+            counts.insert(key.to_owned(), 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{Duration, OffsetDateTime};
+
+    use super::SameSiteCounts;
+    use crate::utils::test as test_utils;
+    use crate::{CookieStore, ManualClock};
+
+    #[test]
+    fn stats() {
+        let clock = ManualClock::new(OffsetDateTime::UNIX_EPOCH);
+        let mut store = CookieStore::default().with_clock(clock.clone());
+        let url = test_utils::url("https://www.example.com/");
+        for cookie in [
+            "session=1; Secure; HttpOnly",
+            "short=22; Max-Age=60; SameSite=Strict",
+            "long=333; Max-Age=3600; SameSite=Lax; Secure",
+            "shared=4444; Domain=example.com; SameSite=None; Secure",
+        ] {
+            store.parse(cookie, &url).unwrap();
+        }
+        let stats = store.stats();
+        assert_eq!(2, stats.session);
+        assert_eq!(2, stats.persistent);
+        assert_eq!(4, stats.unexpired());
+        assert_eq!(0, stats.expired);
+        assert_eq!(8 + 7 + 7 + 10, stats.total_bytes);
+        assert_eq!(3, stats.secure);
+        assert_eq!(1, stats.http_only);
+        assert_eq!(
+            SameSiteCounts {
+                strict: 1,
+                lax: 1,
+                none: 1,
+                unset: 1
+            },
+            stats.same_site
+        );
+        assert_eq!(Some(&3), stats.per_domain.get("www.example.com"));
+        assert_eq!(Some(&1), stats.per_domain.get("example.com"));
+        assert_eq!(None, stats.per_registrable_domain);
+        assert_eq!(
+            Some(OffsetDateTime::UNIX_EPOCH + Duration::minutes(1)),
+            stats.next_expiration
+        );
+
+        clock.advance(Duration::minutes(2));
+        let stats = store.stats();
+        assert_eq!(1, stats.persistent);
+        assert_eq!(1, stats.expired);
+        assert_eq!(Some(&2), stats.per_domain.get("www.example.com"));
+        assert_eq!(
+            Some(OffsetDateTime::UNIX_EPOCH + Duration::hours(1)),
+            stats.next_expiration
+        );
+    }
+
+    #[cfg(feature = "public_suffix")]
+    #[test]
+    fn per_registrable_domain() {
+        let psl: publicsuffix::List = "// ===BEGIN ICANN DOMAINS===\ncom\nuk\nco.uk\n"
+            .parse()
+            .unwrap();
+        let mut store = CookieStore::default().with_suffix_list(psl);
+        for url in [
+            "https://example.co.uk/",
+            "https://shop.example.co.uk/",
+            "https://other.co.uk/",
+        ] {
+            store.parse("cookie1=1", &test_utils::url(url)).unwrap();
+        }
+        let per_registrable_domain = store.stats().per_registrable_domain.unwrap();
+        assert_eq!(2, per_registrable_domain.len());
+        assert_eq!(Some(&2), per_registrable_domain.get("example.co.uk"));
+        assert_eq!(Some(&1), per_registrable_domain.get("other.co.uk"));
+    }
+}
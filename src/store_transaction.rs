@@ -0,0 +1,531 @@
+use std::ops::Deref;
+
+use cookie::Cookie as RawCookie;
+use time::OffsetDateTime;
+use url::Url;
+
+use crate::cookie::Cookie;
+use crate::cookie_store::InsertResult;
+use crate::{
+    CookieQuery, CookieStore, MergeReport, MergeStrategy, ObserverId, SetCookieReport, StoreChange,
+};
+
+/// The state of a single entry prior to a change made within a transaction
+#[derive(Debug, Clone)]
+pub(crate) struct UndoEntry {
+    pub(crate) domain: String,
+    pub(crate) path: String,
+    pub(crate) name: String,
+    /// The `Cookie` stored under `domain`, `path`, and `name` prior to the change, if any
+    pub(crate) prev: Option<Cookie<'static>>,
+}
+
+/// The changes made to a `CookieStore` since its outermost open [`Transaction`] began, most
+/// recent last. Nothing is recorded while no transaction is open.
+#[derive(Debug, Default)]
+pub(crate) struct UndoLog {
+    depth: usize,
+    entries: Vec<UndoEntry>,
+}
+
+impl Clone for UndoLog {
+    /// A clone of a `CookieStore` is not part of any transaction open on the original
+    fn clone(&self) -> UndoLog {
+        UndoLog::default()
+    }
+}
+
+impl UndoLog {
+    /// Returns true if a transaction is open, and so changes should be recorded
+    pub(crate) fn is_active(&self) -> bool {
+        self.depth > 0
+    }
+
+    pub(crate) fn record(
+        &mut self,
+        domain: &str,
+        path: &str,
+        name: &str,
+        prev: Option<Cookie<'static>>,
+    ) {
+        self.entries.push(UndoEntry {
+            domain: domain.to_owned(),
+            path: path.to_owned(),
+            name: name.to_owned(),
+            prev,
+        });
+    }
+
+    /// Open a savepoint, returning the marker to roll back to
+    pub(crate) fn begin(&mut self) -> usize {
+        self.depth += 1;
+        self.entries.len()
+    }
+
+    /// Pop the most recent entry recorded since `savepoint`, if any
+    pub(crate) fn pop_to(&mut self, savepoint: usize) -> Option<UndoEntry> {
+        if self.entries.len() > savepoint {
+            self.entries.pop()
+        } else {
+            None
+        }
+    }
+
+    /// Close the most recently opened savepoint. Entries are retained until the outermost
+    /// savepoint closes, so that an enclosing transaction may still roll them back.
+    pub(crate) fn end(&mut self) {
+        self.depth -= 1;
+        if self.depth == 0 {
+            self.entries.clear();
+        }
+    }
+}
+
+/// A batch of changes to a [`CookieStore`] which is applied atomically: either
+/// [committed](Transaction::commit), or [rolled back](Transaction::rollback), which restores the
+/// store to its state when the transaction began. A transaction dropped without being committed
+/// is rolled back. Begin a transaction with [`CookieStore::transaction`].
+///
+/// A `Transaction` dereferences to its `CookieStore` for read access, and provides the methods of
+/// `CookieStore` which change the store (`insert`, `store_response_cookies`, `remove`, etc.),
+/// including `transaction` to begin a nested transaction (i.e. a savepoint). Committing a nested
+/// transaction makes its changes part of the enclosing transaction, which may still roll them
+/// back. The store itself cannot be replaced within a transaction, as that could not be rolled
+/// back.
+///
+/// Rather than cloning the store, a transaction records the prior state of each entry it
+/// changes, so its cost is proportional to the changes made. Observers are notified of each
+/// change as it is made, and again, with [`ChangeCause::RolledBack`](crate::ChangeCause), as it
+/// is undone.
+///
+/// ```
+/// # use cookie_store::CookieStore;
+/// let mut store = CookieStore::default();
+/// let url = url::Url::parse("https://example.com/").unwrap();
+/// let mut tx = store.transaction();
+/// tx.parse("cookie1=value1", &url).unwrap();
+/// {
+///     let mut nested = tx.transaction();
+///     nested.parse("cookie2=value2", &url).unwrap();
+///     // dropped, so rolled back
+/// }
+/// tx.commit();
+/// assert!(store.contains("example.com", "/", "cookie1"));
+/// assert!(!store.contains("example.com", "/", "cookie2"));
+/// ```
+///
+/// ```compile_fail
+/// # use cookie_store::CookieStore;
+/// let mut store = CookieStore::default();
+/// let mut tx = store.transaction();
+/// // the store cannot be replaced, as that could not be rolled back
+/// *tx = CookieStore::default();
+/// ```
+#[derive(Debug)]
+pub struct Transaction<'a> {
+    store: &'a mut CookieStore,
+    savepoint: usize,
+    finished: bool,
+}
+
+impl<'a> Transaction<'a> {
+    pub(crate) fn new(store: &'a mut CookieStore) -> Transaction<'a> {
+        let savepoint = store.undo_log.begin();
+        Transaction {
+            store,
+            savepoint,
+            finished: false,
+        }
+    }
+
+    /// Apply the changes made in the transaction
+    pub fn commit(mut self) {
+        self.finished = true;
+        self.store.undo_log.end();
+    }
+
+    /// Undo the changes made in the transaction
+    pub fn rollback(mut self) {
+        self.finished = true;
+        self.store.rollback_to(self.savepoint);
+    }
+}
+
+impl Deref for Transaction<'_> {
+    type Target = CookieStore;
+    fn deref(&self) -> &CookieStore {
+        self.store
+    }
+}
+
+/// The changing methods of `CookieStore`, recorded by the transaction
+impl Transaction<'_> {
+    /// See [`CookieStore::transaction`]
+    pub fn transaction(&mut self) -> Transaction<'_> {
+        self.store.transaction()
+    }
+
+    /// See [`CookieStore::store_response_cookies`]
+    pub fn store_response_cookies<I: Iterator<Item = RawCookie<'static>>>(
+        &mut self,
+        cookies: I,
+        url: &Url,
+    ) {
+        self.store.store_response_cookies(cookies, url)
+    }
+
+    /// See [`CookieStore::store_response_set_cookie_headers`]
+    pub fn store_response_set_cookie_headers<I>(&mut self, set_cookies: I, url: &Url)
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        self.store
+            .store_response_set_cookie_headers(set_cookies, url)
+    }
+
+    /// See [`CookieStore::store_response_cookies_for_site`]
+    pub fn store_response_cookies_for_site<I: Iterator<Item = RawCookie<'static>>>(
+        &mut self,
+        cookies: I,
+        url: &Url,
+        top_level_site: &Url,
+    ) {
+        self.store
+            .store_response_cookies_for_site(cookies, url, top_level_site)
+    }
+
+    /// See [`CookieStore::store_response_cookies_with_report`]
+    pub fn store_response_cookies_with_report<I: Iterator<Item = RawCookie<'static>>>(
+        &mut self,
+        cookies: I,
+        url: &Url,
+    ) -> SetCookieReport {
+        self.store.store_response_cookies_with_report(cookies, url)
+    }
+
+    /// See [`CookieStore::store_response_cookies_for_site_with_report`]
+    pub fn store_response_cookies_for_site_with_report<I: Iterator<Item = RawCookie<'static>>>(
+        &mut self,
+        cookies: I,
+        url: &Url,
+        top_level_site: &Url,
+    ) -> SetCookieReport {
+        self.store
+            .store_response_cookies_for_site_with_report(cookies, url, top_level_site)
+    }
+
+    /// See [`CookieStore::add_observer`]
+    pub fn add_observer<F>(&mut self, observer: F) -> ObserverId
+    where
+        F: Fn(&StoreChange<'_>) + Send + Sync + 'static,
+    {
+        self.store.add_observer(observer)
+    }
+
+    /// See [`CookieStore::remove_observer`]
+    pub fn remove_observer(&mut self, id: ObserverId) -> bool {
+        self.store.remove_observer(id)
+    }
+
+    /// See [`CookieStore::remove`]
+    pub fn remove(&mut self, domain: &str, path: &str, name: &str) -> Option<Cookie<'static>> {
+        self.store.remove(domain, path, name)
+    }
+
+    /// See [`CookieStore::parse`]
+    pub fn parse(&mut self, cookie_str: &str, request_url: &Url) -> InsertResult {
+        self.store.parse(cookie_str, request_url)
+    }
+
+    /// See [`CookieStore::insert_raw`]
+    pub fn insert_raw(&mut self, cookie: &RawCookie<'_>, request_url: &Url) -> InsertResult {
+        self.store.insert_raw(cookie, request_url)
+    }
+
+    /// See [`CookieStore::insert`]
+    pub fn insert(&mut self, cookie: Cookie<'static>, request_url: &Url) -> InsertResult {
+        self.store.insert(cookie, request_url)
+    }
+
+    /// See [`CookieStore::insert_at`]
+    pub fn insert_at(
+        &mut self,
+        cookie: Cookie<'static>,
+        request_url: &Url,
+        utc_tm: OffsetDateTime,
+    ) -> InsertResult {
+        self.store.insert_at(cookie, request_url, utc_tm)
+    }
+
+    /// See [`CookieStore::insert_for_site`]
+    pub fn insert_for_site(
+        &mut self,
+        cookie: Cookie<'static>,
+        request_url: &Url,
+        top_level_site: &Url,
+    ) -> InsertResult {
+        self.store
+            .insert_for_site(cookie, request_url, top_level_site)
+    }
+
+    /// See [`CookieStore::clear`]
+    pub fn clear(&mut self) {
+        self.store.clear()
+    }
+
+    /// See [`CookieStore::remove_selected`]
+    pub fn remove_selected(&mut self, query: &CookieQuery) -> Vec<Cookie<'static>> {
+        self.store.remove_selected(query)
+    }
+
+    /// See [`CookieStore::expire_selected`]
+    pub fn expire_selected(&mut self, query: &CookieQuery) -> usize {
+        self.store.expire_selected(query)
+    }
+
+    /// See [`CookieStore::merge`]
+    pub fn merge(&mut self, other: CookieStore, strategy: &MergeStrategy) -> MergeReport {
+        self.store.merge(other, strategy)
+    }
+
+    /// See [`CookieStore::merge_cookies`]
+    pub fn merge_cookies<I>(&mut self, cookies: I, strategy: &MergeStrategy) -> MergeReport
+    where
+        I: IntoIterator<Item = Cookie<'static>>,
+    {
+        self.store.merge_cookies(cookies, strategy)
+    }
+
+    /// See [`CookieStore::purge_expired`]
+    pub fn purge_expired(&mut self) -> Vec<Cookie<'static>> {
+        self.store.purge_expired()
+    }
+
+    /// See [`CookieStore::purge_expired_batch`]
+    pub fn purge_expired_batch(&mut self, max: usize) -> Vec<Cookie<'static>> {
+        self.store.purge_expired_batch(max)
+    }
+
+    /// See [`CookieStore::retain`]
+    pub fn retain<F>(&mut self, predicate: F) -> Vec<Cookie<'static>>
+    where
+        F: FnMut(&Cookie<'static>) -> bool,
+    {
+        self.store.retain(predicate)
+    }
+
+    /// See [`CookieStore::remove_domain`]
+    pub fn remove_domain(&mut self, domain: &str) -> Vec<Cookie<'static>> {
+        self.store.remove_domain(domain)
+    }
+
+    /// See [`CookieStore::remove_registrable_domain`]
+    pub fn remove_registrable_domain(&mut self, domain: &str) -> Vec<Cookie<'static>> {
+        self.store.remove_registrable_domain(domain)
+    }
+
+    /// See [`CookieStore::end_session`]
+    pub fn end_session(&mut self) -> Vec<Cookie<'static>> {
+        self.store.end_session()
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.store.rollback_to(self.savepoint);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::utils::test as test_utils;
+    use crate::{ChangeCause, CookieQuery, CookieStore, MergeStrategy};
+
+    fn store() -> CookieStore {
+        test_utils::store(&[
+            ("a=1", "https://example.com/"),
+            ("b=2", "https://example.com/"),
+            ("c=3", "https://example.com/"),
+        ])
+    }
+
+    #[test]
+    fn rollback() {
+        let mut store = store();
+        let before = test_utils::values(store.iter_any());
+        let url = test_utils::url("https://example.com/");
+        let mut tx = store.transaction();
+        tx.parse("a=10", &url).unwrap();
+        tx.parse("b=2; Max-Age=0", &url).unwrap();
+        tx.remove("example.com", "/", "c");
+        tx.parse("d=4", &url).unwrap();
+        tx.store_response_cookies(
+            vec![::cookie::Cookie::parse("e=5").unwrap()].into_iter(),
+            &url,
+        );
+        assert_eq!(
+            vec!["a=10", "d=4", "e=5"],
+            test_utils::values(tx.iter_unexpired())
+        );
+        assert!(tx.contains_any("example.com", "/", "b"));
+        tx.clear();
+        assert!(test_utils::values(tx.iter_any()).is_empty());
+        tx.rollback();
+        assert_eq!(before, test_utils::values(store.iter_any()));
+        assert_eq!(before, test_utils::values(store.iter_unexpired()));
+
+        // dropped without commit
+        store.transaction().parse("a=10", &url).unwrap();
+        assert_eq!(before, test_utils::values(store.iter_any()));
+    }
+
+    #[test]
+    fn commit_and_nesting() {
+        let mut store = store();
+        let url = test_utils::url("https://example.com/");
+        let mut tx = store.transaction();
+        tx.parse("a=10", &url).unwrap();
+        {
+            let mut nested = tx.transaction();
+            nested.parse("b=20", &url).unwrap();
+            nested.commit();
+        }
+        {
+            let mut nested = tx.transaction();
+            nested.parse("c=30", &url).unwrap();
+            nested.rollback();
+        }
+        assert_eq!(
+            vec!["a=10", "b=20", "c=3"],
+            test_utils::values(tx.iter_any())
+        );
+        tx.commit();
+        assert_eq!(
+            vec!["a=10", "b=20", "c=3"],
+            test_utils::values(store.iter_any())
+        );
+
+        let mut tx = store.transaction();
+        {
+            let mut nested = tx.transaction();
+            nested.parse("a=100", &url).unwrap();
+            nested.commit();
+        }
+        let cloned = CookieStore::clone(&tx);
+        assert!(!cloned.undo_log.is_active());
+        tx.rollback();
+        assert_eq!(
+            vec!["a=10", "b=20", "c=3"],
+            test_utils::values(store.iter_any())
+        );
+        assert_eq!(
+            vec!["a=100", "b=20", "c=3"],
+            test_utils::values(cloned.iter_any())
+        );
+        assert!(!store.undo_log.is_active());
+    }
+
+    #[test]
+    fn rollback_observed() {
+        let mut store = store();
+        let causes = Arc::new(Mutex::new(Vec::new()));
+        let observer_causes = causes.clone();
+        store.add_observer(move |change| observer_causes.lock().unwrap().push(change.cause));
+        let url = test_utils::url("https://example.com/");
+        let mut tx = store.transaction();
+        tx.parse("a=10", &url).unwrap();
+        tx.parse("d=4", &url).unwrap();
+        tx.rollback();
+        assert_eq!(
+            vec![
+                ChangeCause::UpdatedExisting,
+                ChangeCause::Inserted,
+                ChangeCause::RolledBack,
+                ChangeCause::RolledBack
+            ],
+            *causes.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn forwarded_methods() {
+        let mut store = store();
+        let before = test_utils::values(store.iter_any());
+        let url = test_utils::url("https://example.com/");
+        let raw = |s: &str| ::cookie::Cookie::parse(s.to_owned()).unwrap();
+        let cookie = |s: &str| test_utils::make_cookie(s, "https://example.com/", None, None);
+        let mut tx = store.transaction();
+
+        let id = tx.add_observer(|_| {});
+        assert!(tx.remove_observer(id));
+        tx.parse("d=4", &url).unwrap();
+        tx.insert_raw(&raw("e=5"), &url).unwrap();
+        tx.insert(cookie("f=6"), &url).unwrap();
+        tx.insert_at(cookie("g=7"), &url, time::OffsetDateTime::now_utc())
+            .unwrap();
+        tx.insert_for_site(cookie("h=8"), &url, &url).unwrap();
+        tx.store_response_cookies(std::iter::once(raw("i=9")), &url);
+        tx.store_response_set_cookie_headers(["j=10"], &url);
+        tx.store_response_cookies_for_site(std::iter::once(raw("k=11")), &url, &url);
+        let report = tx.store_response_cookies_with_report(std::iter::once(raw("l=12")), &url);
+        assert!(report.all_stored());
+        let report = tx.store_response_cookies_for_site_with_report(
+            std::iter::once(raw("m=13")),
+            &url,
+            &url,
+        );
+        assert!(report.all_stored());
+        {
+            let mut nested = tx.transaction();
+            nested.parse("n=14", &url).unwrap();
+            nested.commit();
+        }
+        let mut other = CookieStore::default();
+        other.parse("o=15", &url).unwrap();
+        assert_eq!(1, tx.merge(other, &MergeStrategy::KeepExisting).added);
+        assert_eq!(
+            1,
+            tx.merge_cookies([cookie("p=16")], &MergeStrategy::KeepExisting)
+                .added
+        );
+        assert_eq!(
+            vec![
+                "a=1", "b=2", "c=3", "d=4", "e=5", "f=6", "g=7", "h=8", "i=9", "j=10", "k=11",
+                "l=12", "m=13", "n=14", "o=15", "p=16"
+            ],
+            {
+                let mut values = test_utils::values(tx.iter_any());
+                values.sort_by_key(|v| v[v.find('=').unwrap() + 1..].parse::<u32>().unwrap());
+                values
+            }
+        );
+
+        assert!(tx.remove("example.com", "/", "a").is_some());
+        assert_eq!(1, tx.remove_selected(&CookieQuery::new().name("b")).len());
+        assert_eq!(1, tx.expire_selected(&CookieQuery::new().name("c")));
+        assert_eq!(1, tx.purge_expired_batch(1).len());
+        assert_eq!(1, tx.expire_selected(&CookieQuery::new().name("d")));
+        assert_eq!(1, tx.purge_expired().len());
+        assert_eq!(1, tx.retain(|c| c.name() != "e").len());
+        tx.parse("q=17", &test_utils::url("https://other.example/"))
+            .unwrap();
+        assert_eq!(1, tx.remove_domain("other.example").len());
+        tx.parse("r=18", &test_utils::url("https://www.other.example/"))
+            .unwrap();
+        assert_eq!(1, tx.remove_registrable_domain("other.example").len());
+        tx.parse("s=19; Max-Age=3600", &url).unwrap();
+        assert_eq!(11, tx.end_session().len());
+        assert_eq!(vec!["s=19"], test_utils::values(tx.iter_any()));
+        tx.clear();
+        assert!(test_utils::values(tx.iter_any()).is_empty());
+
+        tx.rollback();
+        assert_eq!(before, test_utils::values(store.iter_any()));
+        assert_eq!(before, test_utils::values(store.iter_unexpired()));
+    }
+}
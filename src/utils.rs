@@ -25,9 +25,25 @@ pub fn is_secure(url: &Url) -> bool {
     }
 }
 
+/// The attributes, i.e. everything after the first `;`, of the Set-Cookie header value
+/// `set_cookie`, in order, as trimmed (name, value) pairs; `value` is `None` if there is no `=`
+pub fn attributes(set_cookie: &str) -> impl Iterator<Item = (&str, Option<&str>)> {
+    set_cookie
+        .split(';')
+        .skip(1)
+        .map(|attribute| match attribute.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim())),
+            None => (attribute.trim(), None),
+        })
+        .filter(|(name, _)| !name.is_empty())
+}
+
 #[cfg(test)]
 pub mod test {
+    use std::borrow::Borrow;
+
     use crate::cookie::Cookie;
+    use crate::CookieStore;
     use time::{Duration, OffsetDateTime};
     use url::Url;
     #[inline]
@@ -55,10 +71,40 @@ pub mod test {
         )
         .unwrap()
     }
+    /// A `CookieStore` of the cookies parsed from each pair of Set-Cookie header and request URL
+    pub fn store(cookies: &[(&str, &str)]) -> CookieStore {
+        let mut store = CookieStore::default();
+        for (cookie, url_str) in cookies {
+            store.parse(cookie, &url(url_str)).unwrap();
+        }
+        store
+    }
+    /// The `name=value` pairs of `cookies`, sorted
+    pub fn values<C, I>(cookies: I) -> Vec<String>
+    where
+        C: Borrow<Cookie<'static>>,
+        I: IntoIterator<Item = C>,
+    {
+        let mut values = cookies
+            .into_iter()
+            .map(|c| format!("{}={}", c.borrow().name(), c.borrow().value()))
+            .collect::<Vec<_>>();
+        values.sort();
+        values
+    }
     #[inline]
     pub fn in_days(days: i64) -> OffsetDateTime {
         OffsetDateTime::now_utc() + Duration::days(days)
     }
+    /// A path named `name` within a fresh, process-unique temporary directory
+    #[cfg(feature = "serde_json")]
+    pub fn temp_path(name: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("cookie_store-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(name)
+    }
     #[inline]
     pub fn in_minutes(mins: i64) -> OffsetDateTime {
         OffsetDateTime::now_utc() + Duration::minutes(mins)